@@ -1,6 +1,8 @@
 \begindata
 KERNELS_TO_LOAD=(
 	'spice/test_gms.tpc',
+	'spice/test_pck.tpc',
+	'spice/test_spacecraft.tpc',
   'spice/maven_cruise.bsp',
   'spice/voyager2_flyby.bsp'
 )
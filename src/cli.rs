@@ -1,6 +1,6 @@
 use clap::Parser;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[clap(about, version, author)]
 pub struct Args {
 	#[clap(long, value_name = "FILE", help = "Meta-kernel file name")]
@@ -16,7 +16,7 @@ pub struct Args {
 	#[clap(
 		long,
 		value_name = "UTC_TIMESTAMP",
-		help = "J2000 time to propagate up to"
+		help = "J2000 time to propagate up to. Propagates backward in time if it precedes --t0"
 	)]
 	pub tfinal: String,
 
@@ -33,6 +33,27 @@ pub struct Args {
 	)]
 	pub atol: Option<f64>,
 
+	#[clap(
+		long,
+		value_name = "SECONDS",
+		help = "Smallest step size for embedded methods. Failing to meet --atol at this size aborts the propagation. Defaults to 1e-6"
+	)]
+	pub min_step: Option<f64>,
+
+	#[clap(
+		long,
+		value_name = "SECONDS",
+		help = "Largest step size for embedded methods. Unbounded by default"
+	)]
+	pub max_step: Option<f64>,
+
+	#[clap(
+		long,
+		value_name = "N",
+		help = "Number of consecutive rejected attempts after which a step of an embedded method fails. Defaults to 100"
+	)]
+	pub max_rejections: Option<usize>,
+
 	#[clap(
 		long,
 		value_delimiter = ',',
@@ -60,6 +81,189 @@ pub struct Args {
 	)]
 	pub attractors: Option<Vec<String>>,
 
+	#[clap(
+		long,
+		value_delimiter = ',',
+		require_value_delimiter = true,
+		min_values = 1,
+		help = "Bodies whose zonal harmonics (J2..Jn) to consider. Coefficients, radii and pole orientation are read from PCKs"
+	)]
+	pub zonal_bodies: Option<Vec<String>>,
+
+	#[clap(
+		long,
+		value_name = "N",
+		help = "Maximum degree n of zonal harmonics to consider. Defaults to 2"
+	)]
+	pub zonal_degree: Option<usize>,
+
+	#[clap(
+		long,
+		value_name = "FILE",
+		requires = "gravity-field-body",
+		help = "Normalized spherical harmonic gravity field coefficients (ICGEM .gfc or PDS SHADR format)"
+	)]
+	pub gravity_field: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "BODY",
+		requires = "gravity-field",
+		help = "NAIF-ID or name of the body whose gravity field --gravity-field describes"
+	)]
+	pub gravity_field_body: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "N",
+		requires = "gravity-field",
+		help = "Degree and order to truncate the gravity field at. Defaults to the file's maximum degree"
+	)]
+	pub gravity_degree: Option<usize>,
+
+	#[clap(
+		long,
+		value_name = "BODY[:AREA:MASS:CR]",
+		value_delimiter = ',',
+		require_value_delimiter = true,
+		min_values = 1,
+		help = "Small bodies subject to solar radiation pressure, with area (m^2), mass (kg) and reflectivity coefficient. If omitted, these are read from BODYnnn_SRP_AREA, BODYnnn_MASS and BODYnnn_SRP_CR in loaded kernels"
+	)]
+	pub srp: Option<Vec<String>>,
+
+	#[clap(
+		long,
+		value_name = "cylindrical|conical",
+		requires = "srp",
+		help = "Shadow model for solar radiation pressure. Bodies and attractors with known radii act as occulters. Defaults to no shadowing"
+	)]
+	pub shadow_model: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "BODY[:AREA:MASS:CD]",
+		value_delimiter = ',',
+		require_value_delimiter = true,
+		min_values = 1,
+		requires = "atmosphere",
+		help = "Small bodies subject to atmospheric drag, with area (m^2), mass (kg) and drag coefficient. If omitted, these are read from BODYnnn_DRAG_AREA, BODYnnn_MASS and BODYnnn_DRAG_CD in loaded kernels"
+	)]
+	pub drag: Option<Vec<String>>,
+
+	#[clap(
+		long,
+		value_name = "BODY",
+		requires = "drag",
+		help = "Body whose atmosphere causes drag. Its radii are read from PCKs"
+	)]
+	pub atmosphere_body: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "exponential:RHO0:H0:SCALE_HEIGHT|table:FILE",
+		requires = "drag",
+		help = "Atmospheric density model - exponential with density (kg/m^3) at reference altitude (km) and scale height (km), or log-linear interpolation of a file of 'altitude (km) density (kg/m^3)' lines"
+	)]
+	pub atmosphere: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "KM",
+		requires = "drag",
+		help = "Altitude above which drag is neglected. Defaults to 1000 km"
+	)]
+	pub drag_max_altitude: Option<f64>,
+
+	#[clap(
+		long,
+		value_name = "BODY[:A1:A2:A3]",
+		value_delimiter = ',',
+		require_value_delimiter = true,
+		min_values = 1,
+		help = "Small bodies (e. g. comets) subject to Marsden-Sekanina non-gravitational forces, with radial, transverse and normal parameters (au/d^2) scaled by the water sublimation function g(r). If omitted, these are read from BODYnnn_A1, BODYnnn_A2 and BODYnnn_A3 in loaded kernels"
+	)]
+	pub comet_forces: Option<Vec<String>>,
+
+	#[clap(
+		long,
+		value_name = "BODY[:A2]",
+		value_delimiter = ',',
+		require_value_delimiter = true,
+		min_values = 1,
+		help = "Small bodies (e. g. asteroids) subject to the transverse Yarkovsky acceleration A2 (au/d^2) * (1 au / r)^2. If omitted, A2 is read from BODYnnn_A2 in loaded kernels"
+	)]
+	pub yarkovsky: Option<Vec<String>>,
+
+	#[clap(
+		long,
+		value_name = "schwarzschild|eih",
+		help = "Post-Newtonian correction - Schwarzschild term of the observing body, or Einstein-Infeld-Hoffmann equations for all bodies and attractors"
+	)]
+	pub relativity: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "FILE",
+		help = "Impulsive maneuvers of small bodies, one 'UTC_TIMESTAMP BODY J2000|VNB|RTN DV1 DV2 DV3' line (delta-v in m/s) each. VNB and RTN are relative to the observing body"
+	)]
+	pub maneuvers: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "FILE",
+		help = "Finite burns of small bodies, one 'START_UTC STOP_UTC BODY THRUST ISP DIRECTION' line (thrust in N, Isp in s) each. DIRECTION is velocity, anti-velocity (relative to the observing body), inertial:X:Y:Z or table:FILE (J2000). Initial masses are those given with --srp or --drag, or read from BODYnnn_MASS otherwise. Dry masses are read from BODYnnn_DRY_MASS, and burns end early once a body's propellant runs out"
+	)]
+	pub thrust_arcs: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "FILE",
+		help = "Events to detect, one 'BODY CONDITION TARGET ACTION' line each. CONDITION is periapsis, apoapsis, distance:KM, ascending-node, descending-node (J2000 XY plane), soi-entry, soi-exit (sphere of influence with respect to the observing body), penumbra or umbra (entering or leaving the target's conical shadow, given its RADII). ACTION is record, stop or maneuver:J2000|VNB|RTN:DV1:DV2:DV3 (delta-v in m/s, VNB and RTN relative to the target)"
+	)]
+	pub events: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "KM",
+		help = "Report local minima of the distance between small bodies and bodies or attractors below this threshold, with relative velocity and whether they are impacts given the RADII of the latter. Written to OUTPUT_close_approaches.csv next to the output file"
+	)]
+	pub close_approaches: Option<f64>,
+
+	#[clap(
+		long,
+		help = "Report umbra and penumbra intervals of small bodies in the conical shadows of bodies and attractors with known RADII (except the Sun), detected during propagation like events. Written to OUTPUT_eclipses.csv next to the output file"
+	)]
+	pub eclipses: bool,
+
+	#[clap(
+		long,
+		help = "Propagate the 6x6 state transition matrices of small bodies with the variational equations. Written to OUTPUT_stm.csv next to the output file for each saved state, row by row in SI units"
+	)]
+	pub stm: bool,
+
+	#[clap(
+		long,
+		value_name = "FILE",
+		help = "Initial covariances of small bodies at --t0, one 'BODY C11 C21 C22 .. C66' line each (lower triangle of the J2000 position and velocity covariance in km^2, km^2/s and km^2/s^2). They are mapped to each saved state with the state transition matrix and written in J2000 and RTN (relative to the observing body) to OUTPUT_covariance.csv or .oem next to the output file"
+	)]
+	pub covariance: Option<String>,
+
+	#[clap(
+		long,
+		value_name = "M2_S3",
+		requires = "covariance",
+		help = "Spectral density of white noise accelerations along each J2000 axis (m^2/s^3) whose effect is added to the mapped covariances. Defaults to no process noise"
+	)]
+	pub process_noise: Option<f64>,
+
+	#[clap(
+		long,
+		value_name = "csv|oem",
+		requires = "covariance",
+		help = "Format of mapped covariances - CSV or a CCSDS OEM with states and covariance blocks, split into segments wherever the output SPK is. Defaults to csv"
+	)]
+	pub covariance_format: Option<String>,
+
 	#[clap(short, long, value_name = "FILE", help = "File to write results to")]
 	pub output_file: String,
 
@@ -70,6 +274,13 @@ pub struct Args {
 	)]
 	pub fts: Option<f32>,
 
+	#[clap(
+		long,
+		value_name = "SECONDS",
+		help = "Interval between states saved to SPK file, interpolated independently of the integration steps. Defaults to saving the state at each step"
+	)]
+	pub output_step: Option<f64>,
+
 	#[clap(
 		long,
 		value_name = "NAIF_ID",
@@ -77,6 +288,32 @@ pub struct Args {
 	)]
 	pub cb_id: Option<i32>,
 
-	#[clap(long, value_name = "rk4|dopri45|euler", help = "Integration method")]
+	#[clap(
+		long,
+		value_name = "rk4|dopri45|dop853|ias15|rkf78|abm|gauss-jackson|bulirsch-stoer|leapfrog|yoshida4|yoshida6|wisdom-holman|euler",
+		help = "Integration method. dop853 is recommended, as it needs the fewest steps at tight tolerances"
+	)]
 	pub method: Option<String>,
 }
+
+/// Parse a body specification of the form 'BODY' or 'BODY:P1:..:PN', where the format of the
+/// parameters is described by 'params' (e. g. "AREA:MASS:CR")
+pub fn parse_body_spec<const N: usize>(
+	spec: &str,
+	params: &str,
+) -> Result<(String, Option<[f64; N]>), String> {
+	let fields = spec.split(':').collect::<Vec<_>>();
+	let invalid = || format!("Invalid specification '{spec}' - Expected BODY or BODY:{params}");
+
+	match fields[..] {
+		[body] => Ok((body.to_string(), None)),
+		[body, ref values @ ..] if values.len() == N => {
+			let mut parsed = [0f64; N];
+			for (p, v) in parsed.iter_mut().zip(values) {
+				*p = v.parse().map_err(|_| invalid())?;
+			}
+			Ok((body.to_string(), Some(parsed)))
+		}
+		_ => Err(invalid()),
+	}
+}
@@ -0,0 +1,4 @@
+/// NAIF-ID of the Sun
+pub const SUN: i32 = 10;
+/// Astronomical unit in m
+pub const AU: f64 = 1.495978707e11;
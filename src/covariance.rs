@@ -0,0 +1,189 @@
+use crate::maneuvers::ManeuverFrame;
+use crate::ode::STATE_SIZE;
+use crate::propagate::Segment;
+use crate::spice_utils;
+use crate::variational;
+use ndarray::{s, Array1, Array2};
+
+/// Conversion factor of covariances from km^2, km^2/s and km^2/s^2 to m^2, m^2/s and m^2/s^2
+pub const KM2_TO_M2: f64 = 1e6;
+
+/// Covariance of a small body's state at the start of the propagation
+pub struct InitialCovariance {
+	pub body: i32,
+	/// Covariance of J2000 position and velocity in m^2, m^2/s and m^2/s^2
+	pub matrix: Array2<f64>,
+}
+
+impl InitialCovariance {
+	/// Parse a line of the form 'BODY C11 C21 C22 C31 .. C66' - the lower triangle of the
+	/// covariance row by row, in km^2, km^2/s and km^2/s^2 as in CCSDS OEM covariance blocks
+	pub fn parse(line: &str) -> Result<Self, String> {
+		let invalid = || {
+			format!("Invalid covariance '{line}' - Expected BODY and 21 values of the lower triangle C11 C21 C22 .. C66")
+		};
+
+		let fields = line.split_whitespace().collect::<Vec<_>>();
+		let (body, values) = match fields[..] {
+			[body, ref values @ ..] if values.len() == 21 => (body, values),
+			_ => return Err(invalid()),
+		};
+
+		let mut matrix = Array2::zeros((6, 6));
+		let mut values = values.iter();
+		for i in 0..6 {
+			for j in 0..=i {
+				let value = values
+					.next()
+					.unwrap()
+					.parse::<f64>()
+					.map_err(|_| invalid())?;
+				matrix[[i, j]] = value * KM2_TO_M2;
+				matrix[[j, i]] = value * KM2_TO_M2;
+			}
+		}
+
+		Ok(Self {
+			body: spice_utils::naif_ids(&[body])?[0],
+			matrix,
+		})
+	}
+
+	/// Read covariances from a file with one covariance per line.
+	/// Empty lines and lines starting with '#' are ignored
+	pub fn from_file(path: &str) -> Result<Vec<Self>, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("Could not read covariance file '{path}': {e}"))?;
+
+		contents
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(Self::parse)
+			.collect()
+	}
+}
+
+/// Covariance of a small body's state at an output epoch
+pub struct MappedCovariance {
+	pub et: f64,
+	pub body: i32,
+	/// State of the body relative to the observing body
+	pub state: Array1<f64>,
+	/// Covariance of J2000 position and velocity in m^2, m^2/s and m^2/s^2
+	pub j2000: Array2<f64>,
+	/// Covariance in the RTN frame relative to the observing body
+	pub rtn: Array2<f64>,
+}
+
+/// Covariance of the position and velocity errors accumulated over dt (s) due to white noise
+/// accelerations with spectral density q (m^2/s^3) along each axis. Going backward in time, dt is
+/// negative, and so is the correlation of position and velocity errors
+pub fn process_noise(q: f64, dt: f64) -> Array2<f64> {
+	let mut noise = Array2::zeros((6, 6));
+	for i in 0..3 {
+		noise[[i, i]] = q * dt.abs().powi(3) / 3f64;
+		noise[[i, i + 3]] = q * dt * dt.abs() / 2f64;
+		noise[[i + 3, i]] = q * dt * dt.abs() / 2f64;
+		noise[[i + 3, i + 3]] = q * dt.abs();
+	}
+	noise
+}
+
+/// Rotate a covariance of position and velocity into the frame with the given axes (unit vectors)
+pub fn rotate(covariance: &Array2<f64>, axes: &[Array1<f64>; 3]) -> Array2<f64> {
+	let mut rotation = Array2::zeros((6, 6));
+	for (i, axis) in axes.iter().enumerate() {
+		rotation.slice_mut(s![i, ..3]).assign(axis);
+		rotation.slice_mut(s![i + 3, 3..]).assign(axis);
+	}
+	rotation.dot(covariance).dot(&rotation.t())
+}
+
+/// Inverse of a square matrix by Gauss-Jordan elimination with partial pivoting
+fn inverse(matrix: &Array2<f64>) -> Result<Array2<f64>, String> {
+	let n = matrix.nrows();
+	let mut a = matrix.clone();
+	let mut inv = Array2::<f64>::eye(n);
+
+	for col in 0..n {
+		let pivot = (col..n)
+			.max_by(|&i, &j| a[[i, col]].abs().partial_cmp(&a[[j, col]].abs()).unwrap())
+			.unwrap();
+		if a[[pivot, col]] == 0f64 {
+			return Err("State transition matrix is singular".to_string());
+		}
+		for k in 0..n {
+			a.swap([col, k], [pivot, k]);
+			inv.swap([col, k], [pivot, k]);
+		}
+
+		let scale = a[[col, col]];
+		a.row_mut(col).mapv_inplace(|x| x / scale);
+		inv.row_mut(col).mapv_inplace(|x| x / scale);
+
+		for row in (0..n).filter(|&row| row != col) {
+			let factor = a[[row, col]];
+			if factor != 0f64 {
+				let (a_col, inv_col) = (a.row(col).to_owned(), inv.row(col).to_owned());
+				a.row_mut(row).scaled_add(-factor, &a_col);
+				inv.row_mut(row).scaled_add(-factor, &inv_col);
+			}
+		}
+	}
+	Ok(inv)
+}
+
+/// Map the initial covariance of a small body to each state of the segments with its state
+/// transition matrix, the k-th of those propagated. bodies is the number of bodies in the state
+/// vector, idx the index of the small body among them and cb_idx that of the observing body, if it
+/// is propagated. With a spectral density q of process noise, the noise accumulated between
+/// consecutive states is mapped back to the start and added to the initial covariance. Returns the
+/// mapped covariances segment by segment, so each segment after the first starts with the state
+/// after the maneuver at the epoch the previous one ended at
+pub fn map(
+	segments: &[Segment],
+	bodies: usize,
+	idx: usize,
+	cb_idx: Option<usize>,
+	k: usize,
+	initial: &InitialCovariance,
+	q: Option<f64>,
+) -> Result<Vec<Vec<MappedCovariance>>, String> {
+	let mut mapped = Vec::with_capacity(segments.len());
+	// Initial covariance including the process noise accumulated up to the last state
+	let mut accumulated = initial.matrix.clone();
+	let mut last_et = None;
+
+	for segment in segments {
+		let mut segment_mapped = Vec::with_capacity(segment.states.len());
+		for (&et, state) in segment.ets.iter().zip(&segment.states) {
+			let stm = variational::stm(state, bodies, k);
+			if let (Some(q), Some(last_et)) = (q, last_et) {
+				let stm_inv = inverse(&stm)?;
+				accumulated += &stm_inv
+					.dot(&process_noise(q, et - last_et))
+					.dot(&stm_inv.t());
+			}
+			last_et = Some(et);
+
+			let mut rel_state = state
+				.slice(s![(idx * STATE_SIZE)..(idx * STATE_SIZE + 6)])
+				.to_owned();
+			if let Some(cb_idx) = cb_idx {
+				rel_state -= &state.slice(s![(cb_idx * STATE_SIZE)..(cb_idx * STATE_SIZE + 6)]);
+			}
+
+			let j2000 = stm.dot(&accumulated).dot(&stm.t());
+			segment_mapped.push(MappedCovariance {
+				et,
+				body: initial.body,
+				rtn: rotate(&j2000, &ManeuverFrame::Rtn.axes(&rel_state)),
+				state: rel_state,
+				j2000,
+			});
+		}
+		mapped.push(segment_mapped);
+	}
+	Ok(mapped)
+}
@@ -0,0 +1,204 @@
+use crate::spice_utils;
+use ndarray::{arr1, Array1};
+
+/// Atmospheric density as a function of altitude
+pub trait DensityModel {
+	/// Density in kg/m^3 at altitude in m
+	fn density(&self, altitude: f64) -> f64;
+}
+
+/// Density decaying exponentially with altitude above a reference altitude
+pub struct Exponential {
+	/// Density at reference altitude in kg/m^3
+	pub rho0: f64,
+	/// Reference altitude in m
+	pub h0: f64,
+	/// Scale height in m
+	pub scale_height: f64,
+}
+
+impl DensityModel for Exponential {
+	fn density(&self, altitude: f64) -> f64 {
+		self.rho0 * (-(altitude - self.h0) / self.scale_height).exp()
+	}
+}
+
+/// Density interpolated log-linearly between tabulated altitudes. Zero above the highest altitude,
+/// constant below the lowest
+pub struct Tabulated {
+	/// Altitudes in m, ascending
+	pub altitudes: Vec<f64>,
+	/// Densities in kg/m^3
+	pub densities: Vec<f64>,
+}
+
+impl Tabulated {
+	/// Load table from a text file with one 'altitude (km) density (kg/m^3)' pair per line.
+	/// Empty lines and lines starting with '#' are ignored
+	pub fn from_file(path: &str) -> Result<Self, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("Could not read density table '{path}': {e}"))?;
+
+		let (mut altitudes, mut densities) = (Vec::new(), Vec::new());
+		for line in contents.lines().map(str::trim) {
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let values = line
+				.split_whitespace()
+				.map(str::parse::<f64>)
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(|_| format!("Invalid line in density table '{path}': '{line}'"))?;
+			match values[..] {
+				[altitude, density] if density > 0f64 => {
+					altitudes.push(altitude * 1000f64);
+					densities.push(density);
+				}
+				_ => return Err(format!("Invalid line in density table '{path}': '{line}'")),
+			}
+		}
+
+		if altitudes.is_empty() {
+			return Err(format!("Density table '{path}' is empty"));
+		} else if altitudes.windows(2).any(|w| w[0] >= w[1]) {
+			return Err(format!(
+				"Altitudes in density table '{path}' must be ascending"
+			));
+		}
+
+		Ok(Self {
+			altitudes,
+			densities,
+		})
+	}
+}
+
+impl DensityModel for Tabulated {
+	fn density(&self, altitude: f64) -> f64 {
+		let n = self.altitudes.len();
+		if altitude > self.altitudes[n - 1] {
+			return 0f64;
+		} else if altitude <= self.altitudes[0] {
+			return self.densities[0];
+		}
+
+		let i = self.altitudes.partition_point(|&h| h < altitude) - 1;
+		let frac = (altitude - self.altitudes[i]) / (self.altitudes[i + 1] - self.altitudes[i]);
+		(self.densities[i].ln() * (1f64 - frac) + self.densities[i + 1].ln() * frac).exp()
+	}
+}
+
+/// Parse a CLI density model specification: 'exponential:RHO0:H0:SCALE_HEIGHT' with density in
+/// kg/m^3 and altitudes in km, or 'table:FILE'
+pub fn density_model_from_spec(spec: &str) -> Result<Box<dyn DensityModel>, String> {
+	let invalid = || {
+		format!("Invalid density model '{spec}' - Expected exponential:RHO0:H0:SCALE_HEIGHT or table:FILE")
+	};
+
+	match spec.split_once(':') {
+		Some(("exponential", params)) => {
+			let params = params
+				.split(':')
+				.map(str::parse::<f64>)
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(|_| invalid())?;
+			match params[..] {
+				[rho0, h0, scale_height] => Ok(Box::new(Exponential {
+					rho0,
+					h0: h0 * 1000f64,
+					scale_height: scale_height * 1000f64,
+				})),
+				_ => Err(invalid()),
+			}
+		}
+		Some(("table", path)) => Ok(Box::new(Tabulated::from_file(path)?)),
+		_ => Err(invalid()),
+	}
+}
+
+/// Area (m^2), mass (kg) and drag coefficient
+pub type Parameters = [f64; 3];
+
+/// Drag parameters of a single small body
+pub struct Spacecraft {
+	/// Index of the body in the state vector
+	pub idx: usize,
+	/// Cross-sectional area in m^2
+	pub area: f64,
+	/// Mass in kg, unless tracked in the state vector
+	pub mass: f64,
+	/// Drag coefficient
+	pub cd: f64,
+}
+
+impl Spacecraft {
+	/// Use the given parameters (area, mass, Cd) or retrieve them from the kernel pool
+	/// (BODYnnn_DRAG_AREA, BODYnnn_MASS, BODYnnn_DRAG_CD)
+	pub fn new(body: i32, idx: usize, params: Option<Parameters>) -> Result<Self, String> {
+		let [area, mass, cd] = match params {
+			Some(params) => params,
+			None => [
+				spice_utils::body_constants(body, "DRAG_AREA", 1)?[0],
+				spice_utils::body_constants(body, "MASS", 1)?[0],
+				spice_utils::body_constants(body, "DRAG_CD", 1)?[0],
+			],
+		};
+		Ok(Self {
+			idx,
+			area,
+			mass,
+			cd,
+		})
+	}
+}
+
+/// Drag exerted on small bodies by the atmosphere of a single body
+pub struct DragModel {
+	/// Body whose atmosphere to consider
+	pub body: i32,
+	/// Body-fixed frame the atmosphere co-rotates with
+	pub frame: String,
+	/// Equatorial and polar radius of the body in m
+	pub radii: (f64, f64),
+	pub density: Box<dyn DensityModel>,
+	/// Altitude in m above which drag is neglected
+	pub max_altitude: f64,
+	pub spacecraft: Vec<Spacecraft>,
+}
+
+impl DragModel {
+	/// Altitude above the body's reference ellipsoid, measured along the radius vector
+	pub fn altitude(&self, r: &[f64; 3]) -> f64 {
+		let (a, b) = self.radii;
+		let r_norm = r.iter().map(|x| x * x).sum::<f64>().sqrt();
+		let sin_lat = r[2] / r_norm;
+		let cos_lat_sq = 1f64 - sin_lat * sin_lat;
+		r_norm - a * b / (b * b * cos_lat_sq + a * a * sin_lat * sin_lat).sqrt()
+	}
+
+	/// Drag acceleration on a spacecraft of given mass, given its state relative to the body in
+	/// J2000 and the state transformation matrix from J2000 to the body-fixed frame
+	pub fn acceleration(
+		&self,
+		sc: &Spacecraft,
+		mass: f64,
+		rel_state: &Array1<f64>,
+		xform: &[[f64; 6]; 6],
+	) -> Array1<f64> {
+		// State in the body-fixed frame - velocity is relative to the co-rotating atmosphere
+		let bf =
+			[0, 1, 2, 3, 4, 5].map(|i| (0..6).map(|j| xform[i][j] * rel_state[j]).sum::<f64>());
+
+		let altitude = self.altitude(&[bf[0], bf[1], bf[2]]);
+		if altitude > self.max_altitude {
+			return Array1::zeros(3);
+		}
+
+		let v = arr1(&bf[3..6]);
+		let rho = self.density.density(altitude);
+		let a_bf = -0.5 * rho * sc.cd * sc.area / mass * v.dot(&v).sqrt() * &v;
+
+		// Rotate back to J2000 - upper left block of the state transformation is the rotation
+		arr1(&[0, 1, 2].map(|i| (0..3).map(|j| xform[j][i] * a_bf[j]).sum::<f64>()))
+	}
+}
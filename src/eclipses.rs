@@ -0,0 +1,129 @@
+use crate::events;
+use crate::propagate::Segment;
+use crate::srp;
+use ndarray::Array1;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shadow {
+	/// Part of the solar disk is hidden by the occulter
+	Penumbra,
+	/// All of the solar disk is hidden by the occulter
+	Umbra,
+}
+
+impl fmt::Display for Shadow {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Shadow::Penumbra => "penumbra",
+			Shadow::Umbra => "umbra",
+		})
+	}
+}
+
+/// Time span during which a small body is in the shadow of an occulting body. Penumbra intervals
+/// cover all of the time spent in the occulter's shadow, so they contain any umbra intervals
+pub struct Interval {
+	pub body: i32,
+	pub occulter: i32,
+	pub shadow: Shadow,
+	pub start: f64,
+	pub stop: f64,
+}
+
+/// Shadow function of the conical shadow model - the apparent separation of the Sun and occulter
+/// minus the separation at which the shadow is entered, in rad. It is negative within the shadow,
+/// and pi where the occulter can't hide the Sun (see srp::may_occult)
+pub fn shadow_function(
+	shadow: Shadow,
+	to_sun: &Array1<f64>,
+	to_occulter: &Array1<f64>,
+	sun_radius: f64,
+	occulter_radius: f64,
+) -> f64 {
+	if !srp::may_occult(to_sun, to_occulter) {
+		return std::f64::consts::PI;
+	}
+
+	let d_sun = to_sun.dot(to_sun).sqrt();
+	let d_occ = to_occulter.dot(to_occulter).sqrt();
+	let cos_c = to_sun.dot(to_occulter) / (d_sun * d_occ);
+
+	// Apparent radii of Sun and occulter and their apparent separation, as in srp::illumination
+	let a = (sun_radius / d_sun).asin();
+	let b = (occulter_radius / d_occ).min(1f64).asin();
+	let c = cos_c.clamp(-1f64, 1f64).acos();
+
+	match shadow {
+		Shadow::Penumbra => c - (a + b),
+		Shadow::Umbra => c - (b - a),
+	}
+}
+
+/// Umbra and penumbra intervals of small bodies, from the occurrences of shadow events (see
+/// events::Condition::Shadow). events are all events watched for during propagation, of which
+/// those from index first on are reported. Whether a body starts out in a shadow is taken from the
+/// earliest propagated state, as crossings only toggle it. ids are the NAIF-IDs of all bodies in
+/// the state vector, in order. Intervals are clipped to the propagated time span and sorted by
+/// their start
+pub fn intervals(
+	segments: &[Segment],
+	events: &[events::Event],
+	first: usize,
+	occurrences: &[events::Occurrence],
+	ids: &[i32],
+	cb_id: i32,
+) -> Result<Vec<Interval>, String> {
+	// Propagated states in chronological order, which backward propagations produce in reverse
+	let mut states = segments
+		.iter()
+		.flat_map(|segment| segment.ets.iter().cloned().zip(&segment.states))
+		.collect::<Vec<_>>();
+	states.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+	let (earliest, latest) = match (states.first(), states.last()) {
+		(Some(&earliest), Some(&(latest, _))) => (earliest, latest),
+		_ => return Ok(Vec::new()),
+	};
+
+	let mut found = Vec::new();
+	for (i, event) in events.iter().enumerate().skip(first) {
+		let shadow = match event.condition {
+			events::Condition::Shadow(shadow) => shadow,
+			_ => continue,
+		};
+
+		let detector = events::Detector::new(event, ids, cb_id)?;
+		let mut entered = if detector.value(earliest.0, earliest.1)? < 0f64 {
+			Some(earliest.0)
+		} else {
+			None
+		};
+		let mut crossings = occurrences
+			.iter()
+			.filter(|occurrence| occurrence.event == i)
+			.map(|occurrence| occurrence.et)
+			.collect::<Vec<_>>();
+		crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+		let interval = |start, stop| Interval {
+			body: event.body,
+			occulter: event.target,
+			shadow,
+			start,
+			stop,
+		};
+		for et in crossings {
+			match entered.take() {
+				Some(start) => found.push(interval(start, et)),
+				None => entered = Some(et),
+			}
+		}
+		// Close intervals still open at the end of the propagation
+		if let Some(start) = entered {
+			found.push(interval(start, latest));
+		}
+	}
+
+	found.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+	Ok(found)
+}
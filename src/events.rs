@@ -0,0 +1,407 @@
+use crate::constants;
+use crate::eclipses::{self, Shadow};
+use crate::maneuvers::ManeuverFrame;
+use crate::ode::STATE_SIZE;
+use crate::spice_utils;
+use ndarray::{s, Array1};
+use std::fmt;
+
+/// Events are located to within this many seconds
+const ROOT_TOLERANCE: f64 = 1e-6;
+const MAX_ROOT_ITERATIONS: usize = 100;
+
+/// Geometric condition defining an event, in terms of a body's state relative to a target body
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Condition {
+	/// Closest approach to the target
+	Periapsis,
+	/// Farthest point from the target
+	Apoapsis,
+	/// Distance from the target (m) crossing a threshold, in either direction
+	Distance(f64),
+	/// Crossing the target's J2000 XY plane northward
+	AscendingNode,
+	/// Crossing the target's J2000 XY plane southward
+	DescendingNode,
+	/// Entering the target's sphere of influence with respect to the observing body
+	SoiEntry,
+	/// Leaving the target's sphere of influence with respect to the observing body
+	SoiExit,
+	/// Entering or leaving the target's conical shadow
+	Shadow(Shadow),
+}
+
+/// Direction in which the event function changes sign at an event, forward in time
+enum Crossing {
+	Increasing,
+	Decreasing,
+	Either,
+}
+
+impl Condition {
+	/// Parse 'periapsis', 'apoapsis', 'distance:KM', 'ascending-node', 'descending-node',
+	/// 'soi-entry', 'soi-exit', 'penumbra' or 'umbra'
+	pub fn from_spec(spec: &str) -> Result<Self, String> {
+		let invalid = || {
+			format!("Invalid event condition '{spec}' - Expected periapsis, apoapsis, distance:KM, ascending-node, descending-node, soi-entry, soi-exit, penumbra or umbra")
+		};
+
+		match spec.split_once(':') {
+			None => match spec {
+				"periapsis" => Ok(Self::Periapsis),
+				"apoapsis" => Ok(Self::Apoapsis),
+				"ascending-node" => Ok(Self::AscendingNode),
+				"descending-node" => Ok(Self::DescendingNode),
+				"soi-entry" => Ok(Self::SoiEntry),
+				"soi-exit" => Ok(Self::SoiExit),
+				"penumbra" => Ok(Self::Shadow(Shadow::Penumbra)),
+				"umbra" => Ok(Self::Shadow(Shadow::Umbra)),
+				_ => Err(invalid()),
+			},
+			Some(("distance", km)) => match km.parse::<f64>() {
+				Ok(km) if km > 0f64 => Ok(Self::Distance(km * 1000f64)),
+				_ => Err(invalid()),
+			},
+			_ => Err(invalid()),
+		}
+	}
+
+	fn crossing(&self) -> Crossing {
+		match self {
+			Self::Periapsis | Self::AscendingNode | Self::SoiExit => Crossing::Increasing,
+			Self::Apoapsis | Self::DescendingNode | Self::SoiEntry => Crossing::Decreasing,
+			Self::Distance(_) | Self::Shadow(_) => Crossing::Either,
+		}
+	}
+}
+
+impl fmt::Display for Condition {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Periapsis => write!(f, "periapsis"),
+			Self::Apoapsis => write!(f, "apoapsis"),
+			Self::Distance(distance) => write!(f, "distance:{}", distance / 1000f64),
+			Self::AscendingNode => write!(f, "ascending-node"),
+			Self::DescendingNode => write!(f, "descending-node"),
+			Self::SoiEntry => write!(f, "soi-entry"),
+			Self::SoiExit => write!(f, "soi-exit"),
+			Self::Shadow(shadow) => write!(f, "{shadow}"),
+		}
+	}
+}
+
+/// What to do when an event occurs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+	/// Only report the event
+	Record,
+	/// End the propagation at the event
+	Stop,
+	/// Impulsive maneuver of the body (delta-v in m/s), with VNB and RTN relative to the target
+	Maneuver(ManeuverFrame, [f64; 3]),
+}
+
+impl Action {
+	/// Parse 'record', 'stop' or 'maneuver:J2000|VNB|RTN:DV1:DV2:DV3'
+	pub fn from_spec(spec: &str) -> Result<Self, String> {
+		let invalid = || {
+			format!("Invalid event action '{spec}' - Expected record, stop or maneuver:J2000|VNB|RTN:DV1:DV2:DV3")
+		};
+
+		match spec.split(':').collect::<Vec<_>>()[..] {
+			["record"] => Ok(Self::Record),
+			["stop"] => Ok(Self::Stop),
+			["maneuver", frame, dv1, dv2, dv3] => {
+				let frame = match frame.to_uppercase().as_str() {
+					"J2000" => ManeuverFrame::J2000,
+					"VNB" => ManeuverFrame::Vnb,
+					"RTN" => ManeuverFrame::Rtn,
+					_ => return Err(invalid()),
+				};
+				let mut dv = [0f64; 3];
+				for (p, v) in dv.iter_mut().zip([dv1, dv2, dv3]) {
+					*p = v.parse().map_err(|_| invalid())?;
+				}
+				Ok(Self::Maneuver(frame, dv))
+			}
+			_ => Err(invalid()),
+		}
+	}
+}
+
+/// Event to watch for during propagation
+#[derive(Clone, Debug)]
+pub struct Event {
+	pub body: i32,
+	pub condition: Condition,
+	pub target: i32,
+	pub action: Action,
+}
+
+impl Event {
+	/// Parse a line of the form 'BODY CONDITION TARGET ACTION'
+	pub fn parse(line: &str) -> Result<Self, String> {
+		let fields = line.split_whitespace().collect::<Vec<_>>();
+		let (body, condition, target, action) = match fields[..] {
+			[body, condition, target, action] => (body, condition, target, action),
+			_ => {
+				return Err(format!(
+					"Invalid event '{line}' - Expected BODY CONDITION TARGET ACTION"
+				))
+			}
+		};
+
+		let body = spice_utils::naif_ids(&[body])?[0];
+		let target = spice_utils::naif_ids(&[target])?[0];
+		if body == target {
+			return Err(format!(
+				"Invalid event '{line}' - Body and target must differ"
+			));
+		}
+
+		Ok(Self {
+			body,
+			condition: Condition::from_spec(condition)?,
+			target,
+			action: Action::from_spec(action)?,
+		})
+	}
+
+	/// Read events from a file with one event per line.
+	/// Empty lines and lines starting with '#' are ignored
+	pub fn from_file(path: &str) -> Result<Vec<Self>, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("Could not read event file '{path}': {e}"))?;
+
+		contents
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(Self::parse)
+			.collect()
+	}
+}
+
+/// Event that occurred during propagation
+#[derive(Clone, Debug)]
+pub struct Occurrence {
+	pub et: f64,
+	/// Index of the event among those watched for
+	pub event: usize,
+	/// State of the body relative to the target at the event
+	pub relative_state: Array1<f64>,
+}
+
+/// Event function of an event, resolved against the layout of the state vector
+pub struct Detector {
+	pub event: Event,
+	body_idx: usize,
+	/// Index of the target in the state vector, if it is propagated
+	target_idx: Option<usize>,
+	cb_id: i32,
+	cb_idx: Option<usize>,
+	/// Ratio of the radius of the target's sphere of influence to its distance from the
+	/// observing body
+	soi_ratio: f64,
+	sun_idx: Option<usize>,
+	/// Radii of the Sun and the target in m, if it casts a shadow
+	shadow_radii: (f64, f64),
+}
+
+impl Detector {
+	/// ids are the NAIF-IDs of all bodies in the state vector, in order
+	pub fn new(event: &Event, ids: &[i32], cb_id: i32) -> Result<Self, String> {
+		let body_idx = ids.iter().position(|&id| id == event.body).ok_or_else(|| {
+			format!(
+				"Events can only be detected for propagated bodies, {} is not one",
+				event.body
+			)
+		})?;
+
+		let soi_ratio = match event.condition {
+			Condition::SoiEntry | Condition::SoiExit => {
+				if event.target == cb_id {
+					return Err(format!(
+						"The sphere of influence of {} is defined with respect to the observing body {cb_id}, which must differ",
+						event.target
+					));
+				}
+				(spice_utils::mu(event.target)? / spice_utils::mu(cb_id)?).powf(0.4)
+			}
+			_ => 0f64,
+		};
+
+		let shadow_radii = match event.condition {
+			Condition::Shadow(_) => {
+				if event.target == constants::SUN {
+					return Err(format!(
+						"Invalid {} event - The Sun casts no shadow",
+						event.condition
+					));
+				}
+				let radius =
+					|id| Ok::<_, String>(spice_utils::body_constants(id, "RADII", 3)?[0] * 1000f64);
+				(radius(constants::SUN)?, radius(event.target)?)
+			}
+			_ => (0f64, 0f64),
+		};
+
+		Ok(Self {
+			event: event.clone(),
+			body_idx,
+			target_idx: ids.iter().position(|&id| id == event.target),
+			cb_id,
+			cb_idx: ids.iter().position(|&id| id == cb_id),
+			soi_ratio,
+			sun_idx: ids.iter().position(|&id| id == constants::SUN),
+			shadow_radii,
+		})
+	}
+
+	/// State of a body relative to the origin of the state vector, which is the observing body
+	/// at the start of the propagation
+	fn state_of(
+		&self,
+		idx: Option<usize>,
+		id: i32,
+		et: f64,
+		y: &Array1<f64>,
+	) -> Result<Array1<f64>, String> {
+		match idx {
+			Some(idx) => Ok(y
+				.slice(s![(idx * STATE_SIZE)..(idx * STATE_SIZE + 6)])
+				.to_owned()),
+			None => {
+				let cb_state = self.state_of_cb(y);
+				Ok(spice_utils::state_at_instant(id, self.cb_id, et)? + cb_state)
+			}
+		}
+	}
+
+	fn state_of_cb(&self, y: &Array1<f64>) -> Array1<f64> {
+		match self.cb_idx {
+			Some(idx) => y
+				.slice(s![(idx * STATE_SIZE)..(idx * STATE_SIZE + 6)])
+				.to_owned(),
+			None => Array1::zeros(6),
+		}
+	}
+
+	/// State of the body relative to the target
+	pub fn relative_state(&self, et: f64, y: &Array1<f64>) -> Result<Array1<f64>, String> {
+		let body = self.state_of(Some(self.body_idx), self.event.body, et, y)?;
+		let target = if self.event.target == self.cb_id {
+			self.state_of_cb(y)
+		} else {
+			self.state_of(self.target_idx, self.event.target, et, y)?
+		};
+		Ok(body - target)
+	}
+
+	/// Value of the event function, which changes sign at the event
+	pub fn value(&self, et: f64, y: &Array1<f64>) -> Result<f64, String> {
+		let rel = self.relative_state(et, y)?;
+		let r = rel.slice(s![..3]);
+		let v = rel.slice(s![3..6]);
+		let distance = r.dot(&r).sqrt();
+
+		Ok(match self.event.condition {
+			Condition::Periapsis | Condition::Apoapsis => r.dot(&v),
+			Condition::Distance(threshold) => distance - threshold,
+			Condition::AscendingNode | Condition::DescendingNode => r[2],
+			Condition::SoiEntry | Condition::SoiExit => {
+				let target =
+					self.state_of(self.target_idx, self.event.target, et, y)? - self.state_of_cb(y);
+				let target_distance = target.slice(s![..3]).dot(&target.slice(s![..3])).sqrt();
+				distance - self.soi_ratio * target_distance
+			}
+			Condition::Shadow(shadow) => {
+				let body = self.state_of(Some(self.body_idx), self.event.body, et, y)?;
+				let sun = if self.cb_id == constants::SUN {
+					self.state_of_cb(y)
+				} else {
+					self.state_of(self.sun_idx, constants::SUN, et, y)?
+				};
+				let to_sun = (sun - body).slice(s![..3]).to_owned();
+				let (sun_radius, radius) = self.shadow_radii;
+				eclipses::shadow_function(shadow, &to_sun, &-&r, sun_radius, radius)
+			}
+		})
+	}
+
+	/// Whether the event occurs between two consecutive epochs, given as (epoch, value of the event
+	/// function). The epochs may be in either order
+	pub fn triggered(&self, (x0, g0): (f64, f64), (x1, g1): (f64, f64)) -> bool {
+		let (earlier, later) = if x0 < x1 { (g0, g1) } else { (g1, g0) };
+		let increasing = earlier < 0f64 && later >= 0f64;
+		let decreasing = earlier > 0f64 && later <= 0f64;
+		match self.event.condition.crossing() {
+			Crossing::Increasing => increasing,
+			Crossing::Decreasing => decreasing,
+			Crossing::Either => increasing || decreasing,
+		}
+	}
+}
+
+/// Events occurring within a step from x0 to x1 as pairs of their epoch and the index of their
+/// detector, in order of occurrence. Takes the values of the event functions at both ends, and the
+/// interpolated state at epochs in between
+pub fn scan<'a>(
+	detectors: impl Iterator<Item = &'a Detector>,
+	(x0, values0): (f64, &[f64]),
+	(x1, values1): (f64, &[f64]),
+	interpolate: impl Fn(f64) -> Result<Array1<f64>, String>,
+) -> Result<Vec<(f64, usize)>, String> {
+	let mut found = Vec::new();
+	for (i, detector) in detectors.enumerate() {
+		let (g0, g1) = (values0[i], values1[i]);
+		if detector.triggered((x0, g0), (x1, g1)) {
+			let g = |x: f64| detector.value(x, &interpolate(x)?);
+			found.push((locate(g, (x0, g0), (x1, g1))?, i));
+		}
+	}
+	found.sort_by(|a, b| (a.0 - x0).abs().partial_cmp(&(b.0 - x0).abs()).unwrap());
+	Ok(found)
+}
+
+/// Find the epoch between x0 and x1 at which g changes sign, given its values there, with the
+/// Illinois variant of regula falsi
+pub fn locate(
+	g: impl Fn(f64) -> Result<f64, String>,
+	(mut x0, mut g0): (f64, f64),
+	(mut x1, mut g1): (f64, f64),
+) -> Result<f64, String> {
+	if g1 == 0f64 {
+		return Ok(x1);
+	}
+
+	// Side of the bracket retained in the last iteration
+	let mut retained = None;
+	for _ in 0..MAX_ROOT_ITERATIONS {
+		if (x1 - x0).abs() <= ROOT_TOLERANCE {
+			break;
+		}
+
+		let x = (x0 * g1 - x1 * g0) / (g1 - g0);
+		let gx = g(x)?;
+		if gx == 0f64 {
+			return Ok(x);
+		} else if (gx < 0f64) == (g1 < 0f64) {
+			// Root lies between x0 and x
+			x1 = x;
+			g1 = gx;
+			if retained == Some(0) {
+				g0 *= 0.5;
+			}
+			retained = Some(0);
+		} else {
+			x0 = x;
+			g0 = gx;
+			if retained == Some(1) {
+				g1 *= 0.5;
+			}
+			retained = Some(1);
+		}
+	}
+	Ok(x1)
+}
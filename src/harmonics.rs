@@ -0,0 +1,309 @@
+use crate::spice_utils;
+use ndarray::{arr1, Array1};
+
+/// Zonal part of the gravity field of an oblate body (J2..Jn)
+pub struct ZonalHarmonics {
+	pub body: i32,
+	pub mu: f64,
+	/// Reference radius of the harmonic coefficients in m
+	pub radius: f64,
+	/// Unnormalized coefficients J2..Jn
+	pub js: Vec<f64>,
+	/// Body-fixed frame whose z-axis is the body's pole
+	pub frame: String,
+}
+
+impl ZonalHarmonics {
+	/// Retrieve coefficients, reference radius, GM and body-fixed frame from loaded kernels
+	pub fn from_spice(body: i32, max_degree: usize) -> Result<Self, String> {
+		let (radius, js) = spice_utils::zonal_harmonics(body, max_degree)?;
+		Ok(Self {
+			body,
+			mu: spice_utils::mu(body)?,
+			radius,
+			js,
+			frame: spice_utils::body_fixed_frame(body)?,
+		})
+	}
+
+	/// Unit vector along the body's rotation axis in J2000 at et
+	pub fn pole(&self, et: f64) -> Result<Array1<f64>, String> {
+		// Rows of the rotation matrix are the body-fixed axes expressed in J2000
+		let rot = spice_utils::rotation("J2000", &self.frame, et)?;
+		Ok(arr1(&rot[2]))
+	}
+
+	/// Acceleration (excluding the point-mass term) at position r relative to the body's center
+	pub fn acceleration(&self, r: &Array1<f64>, pole: &Array1<f64>) -> Array1<f64> {
+		let r_norm = r.dot(r).sqrt();
+		let r_hat = r / r_norm;
+		// Sine of latitude
+		let s = r_hat.dot(pole);
+
+		// Legendre polynomials P_n(s) and their derivatives, starting at n = 1
+		let (mut p_prev, mut p) = (1f64, s);
+		let (mut dp_prev, mut dp) = (0f64, 1f64);
+
+		let mut radial = 0f64;
+		let mut polar = 0f64;
+		let mut r_ratio = self.radius / r_norm;
+
+		for (idx, j) in self.js.iter().enumerate() {
+			let n = (idx + 1) as f64;
+			// Advance recursions from degree n to n + 1
+			let p_next = ((2.0 * n + 1.0) * s * p - n * p_prev) / (n + 1.0);
+			let dp_next = dp_prev + (2.0 * n + 1.0) * p;
+			p_prev = p;
+			p = p_next;
+			dp_prev = dp;
+			dp = dp_next;
+
+			r_ratio *= self.radius / r_norm;
+			radial += j * r_ratio * ((n + 2.0) * p + s * dp);
+			polar += j * r_ratio * dp;
+		}
+
+		self.mu / r_norm.powi(2) * (radial * &r_hat - polar * pole)
+	}
+}
+
+/// Full spherical harmonic gravity field of a body, from fully normalized coefficients
+pub struct GravityField {
+	pub body: i32,
+	/// Standard gravitational parameter the coefficients are scaled by
+	pub mu: f64,
+	/// Reference radius of the coefficients in m
+	pub radius: f64,
+	/// Truncation degree (and order)
+	pub degree: usize,
+	/// Normalized coefficients, indexed as c[n][m]
+	pub c: Vec<Vec<f64>>,
+	pub s: Vec<Vec<f64>>,
+	/// Body-fixed frame the coefficients refer to
+	pub frame: String,
+}
+
+impl GravityField {
+	/// Load coefficients from an ICGEM (.gfc) or PDS SHADR file, truncated to max_degree if given
+	pub fn from_file(path: &str, body: i32, max_degree: Option<usize>) -> Result<Self, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("Could not read gravity field file '{path}': {e}"))?;
+
+		let (mu, radius, file_degree, coefficients) = if contents.contains("end_of_head") {
+			parse_icgem(&contents)
+		} else {
+			parse_shadr(&contents)
+		}
+		.map_err(|msg| format!("Could not parse gravity field file '{path}': {msg}"))?;
+
+		let degree = max_degree.unwrap_or(file_degree);
+		if degree > file_degree {
+			return Err(format!(
+				"Gravity field file '{path}' only contains coefficients up to degree {file_degree}"
+			));
+		}
+
+		let mut c = (0..=degree).map(|n| vec![0f64; n + 1]).collect::<Vec<_>>();
+		let mut s = c.clone();
+		for (n, m, cnm, snm) in coefficients {
+			if n <= degree && m <= n {
+				c[n][m] = cnm;
+				s[n][m] = snm;
+			}
+		}
+
+		Ok(Self {
+			body,
+			mu,
+			radius,
+			degree,
+			c,
+			s,
+			frame: spice_utils::body_fixed_frame(body)?,
+		})
+	}
+
+	/// Acceleration (excluding the point-mass term) at position r relative to the body's center.
+	/// rot rotates vectors from J2000 into the body-fixed frame
+	pub fn acceleration(&self, r: &Array1<f64>, rot: &[[f64; 3]; 3]) -> Array1<f64> {
+		let r_bf = [0, 1, 2].map(|i| (0..3).map(|j| rot[i][j] * r[j]).sum::<f64>());
+		let a_bf = self.acceleration_body_fixed(r_bf);
+		arr1(&[0, 1, 2].map(|i| (0..3).map(|j| rot[j][i] * a_bf[j]).sum::<f64>()))
+	}
+
+	/// Acceleration in the body-fixed frame, using the normalized recursion of the V/W functions
+	/// (Cunningham's method, see Montenbruck & Gill, Satellite Orbits, 3.2.5)
+	fn acceleration_body_fixed(&self, [x, y, z]: [f64; 3]) -> [f64; 3] {
+		let nmax = self.degree + 1;
+		let r_sq = x * x + y * y + z * z;
+		let rho = self.radius * self.radius / r_sq;
+		let (x0, y0, z0) = (
+			x * self.radius / r_sq,
+			y * self.radius / r_sq,
+			z * self.radius / r_sq,
+		);
+
+		let mut v = (0..=nmax).map(|n| vec![0f64; n + 1]).collect::<Vec<_>>();
+		let mut w = v.clone();
+
+		v[0][0] = self.radius / r_sq.sqrt();
+		for m in 0..=nmax {
+			if m > 0 {
+				// Sectorial terms
+				let fac = if m == 1 {
+					3f64.sqrt()
+				} else {
+					((2 * m + 1) as f64 / (2 * m) as f64).sqrt()
+				};
+				v[m][m] = fac * (x0 * v[m - 1][m - 1] - y0 * w[m - 1][m - 1]);
+				w[m][m] = fac * (x0 * w[m - 1][m - 1] + y0 * v[m - 1][m - 1]);
+			}
+			// Zonal and tesseral terms
+			for n in (m + 1)..=nmax {
+				let (nf, mf) = (n as f64, m as f64);
+				let a = ((2.0 * nf - 1.0) * (2.0 * nf + 1.0) / ((nf - mf) * (nf + mf))).sqrt();
+				v[n][m] = a * z0 * v[n - 1][m];
+				w[n][m] = a * z0 * w[n - 1][m];
+				if n >= m + 2 {
+					let b = ((2.0 * nf + 1.0) * (nf + mf - 1.0) * (nf - mf - 1.0)
+						/ ((2.0 * nf - 3.0) * (nf + mf) * (nf - mf)))
+						.sqrt();
+					v[n][m] -= b * rho * v[n - 2][m];
+					w[n][m] -= b * rho * w[n - 2][m];
+				}
+			}
+		}
+
+		let (mut ax, mut ay, mut az) = (0f64, 0f64, 0f64);
+		for n in 2..=self.degree {
+			let nf = n as f64;
+			for m in 0..=n {
+				let mf = m as f64;
+				let (c, s) = (self.c[n][m], self.s[n][m]);
+
+				let f_z = ((2.0 * nf + 1.0) * (nf + mf + 1.0) * (nf - mf + 1.0) / (2.0 * nf + 3.0))
+					.sqrt();
+				az += f_z * (-c * v[n + 1][m] - s * w[n + 1][m]);
+
+				if m == 0 {
+					let f = ((2.0 * nf + 1.0) * (nf + 1.0) * (nf + 2.0) / (2.0 * (2.0 * nf + 3.0)))
+						.sqrt();
+					ax -= f * c * v[n + 1][1];
+					ay -= f * c * w[n + 1][1];
+				} else {
+					let f_a = ((2.0 * nf + 1.0) * (nf + mf + 1.0) * (nf + mf + 2.0)
+						/ (2.0 * nf + 3.0))
+						.sqrt();
+					let f_b = (2.0 * (2.0 * nf + 1.0) * (nf - mf + 1.0) * (nf - mf + 2.0)
+						/ (if m == 1 { 1.0 } else { 2.0 } * (2.0 * nf + 3.0)))
+						.sqrt();
+					ax += 0.5
+						* (f_a * (-c * v[n + 1][m + 1] - s * w[n + 1][m + 1])
+							+ f_b * (c * v[n + 1][m - 1] + s * w[n + 1][m - 1]));
+					ay += 0.5
+						* (f_a * (-c * w[n + 1][m + 1] + s * v[n + 1][m + 1])
+							+ f_b * (-c * w[n + 1][m - 1] + s * v[n + 1][m - 1]));
+				}
+			}
+		}
+
+		let fac = self.mu / (self.radius * self.radius);
+		[fac * ax, fac * ay, fac * az]
+	}
+}
+
+/// Coefficients as (n, m, Cnm, Snm)
+type Coefficients = Vec<(usize, usize, f64, f64)>;
+
+/// Parse number, accepting Fortran-style exponents (1.0D-03)
+fn parse_f64(s: &str) -> Result<f64, String> {
+	s.replace(&['D', 'd'][..], "E")
+		.parse()
+		.map_err(|_| format!("Invalid number: '{s}'"))
+}
+
+fn parse_usize(s: &str) -> Result<usize, String> {
+	parse_f64(s).map(|f| f as usize)
+}
+
+/// Parse ICGEM format: keyword header terminated by 'end_of_head', then 'gfc n m C S ...' records.
+/// Units are SI
+fn parse_icgem(contents: &str) -> Result<(f64, f64, usize, Coefficients), String> {
+	let (mut mu, mut radius, mut degree) = (None, None, None);
+	let mut lines = contents.lines();
+
+	for line in &mut lines {
+		let fields = line.split_whitespace().collect::<Vec<_>>();
+		match fields[..] {
+			["end_of_head", ..] => break,
+			["earth_gravity_constant", value, ..] | ["gravity_constant", value, ..] => {
+				mu = Some(parse_f64(value)?)
+			}
+			["radius", value, ..] => radius = Some(parse_f64(value)?),
+			["max_degree", value, ..] => degree = Some(parse_usize(value)?),
+			["norm", norm, ..] if norm != "fully_normalized" => {
+				return Err(format!("Unsupported normalization: '{norm}'"))
+			}
+			_ => {}
+		}
+	}
+
+	let mut coefficients = Vec::new();
+	for line in lines {
+		let fields = line.split_whitespace().collect::<Vec<_>>();
+		if let ["gfc", n, m, c, s, ..] = fields[..] {
+			coefficients.push((
+				parse_usize(n)?,
+				parse_usize(m)?,
+				parse_f64(c)?,
+				parse_f64(s)?,
+			));
+		}
+	}
+
+	Ok((
+		mu.ok_or("Missing gravity constant")?,
+		radius.ok_or("Missing radius")?,
+		degree.ok_or("Missing max_degree")?,
+		coefficients,
+	))
+}
+
+/// Parse PDS SHADR format: header record 'radius, GM, sigma GM, degree, order, normalization, ...'
+/// followed by 'n, m, C, S, ...' records. Units are km and km^3/s^2
+fn parse_shadr(contents: &str) -> Result<(f64, f64, usize, Coefficients), String> {
+	let mut records = contents
+		.lines()
+		.map(|line| {
+			line.split(|c: char| c == ',' || c.is_whitespace())
+				.filter(|field| !field.is_empty())
+				.collect::<Vec<_>>()
+		})
+		.filter(|fields| !fields.is_empty());
+
+	let header = records.next().ok_or("Missing header record")?;
+	if header.len() < 6 {
+		return Err("Incomplete header record".to_string());
+	}
+	if parse_usize(header[5])? != 1 {
+		return Err("Coefficients are not normalized".to_string());
+	}
+	let radius = parse_f64(header[0])? * 1000f64;
+	let mu = parse_f64(header[1])? * 1e9;
+	let degree = parse_usize(header[3])?;
+
+	let mut coefficients = Vec::new();
+	for fields in records {
+		if fields.len() < 4 {
+			return Err(format!("Incomplete record: '{}'", fields.join(", ")));
+		}
+		coefficients.push((
+			parse_usize(fields[0])?,
+			parse_usize(fields[1])?,
+			parse_f64(fields[2])?,
+			parse_f64(fields[3])?,
+		));
+	}
+
+	Ok((mu, radius, degree, coefficients))
+}
@@ -1,9 +1,23 @@
 mod cli;
+mod constants;
+mod covariance;
+mod drag;
+mod eclipses;
+mod events;
+mod harmonics;
+mod maneuvers;
+mod nongrav;
 mod ode;
 mod propagate;
+mod relativity;
+mod reports;
 mod run;
 mod solvers;
 mod spice_utils;
+mod srp;
+mod thrust;
+mod variational;
+
 #[cfg(test)]
 mod tests;
 
@@ -0,0 +1,149 @@
+use crate::spice_utils;
+use ndarray::{arr1, s, Array1};
+
+/// Frame in which a maneuver's delta-v is given
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ManeuverFrame {
+	J2000,
+	/// Velocity, orbit normal, binormal - relative to the observing body
+	Vnb,
+	/// Radial, transverse, orbit normal - relative to the observing body
+	Rtn,
+}
+
+impl ManeuverFrame {
+	/// Unit vectors of the frame's axes in J2000, given the body's state relative to the observing
+	/// body
+	pub fn axes(&self, rel_state: &Array1<f64>) -> [Array1<f64>; 3] {
+		let unit = |v: Array1<f64>| &v / v.dot(&v).sqrt();
+		let cross = |a: &Array1<f64>, b: &Array1<f64>| {
+			arr1(&[
+				a[1] * b[2] - a[2] * b[1],
+				a[2] * b[0] - a[0] * b[2],
+				a[0] * b[1] - a[1] * b[0],
+			])
+		};
+
+		let r = rel_state.slice(s![..3]).to_owned();
+		let v = rel_state.slice(s![3..6]).to_owned();
+		let n = unit(cross(&r, &v));
+
+		match self {
+			ManeuverFrame::J2000 => [
+				arr1(&[1f64, 0f64, 0f64]),
+				arr1(&[0f64, 1f64, 0f64]),
+				arr1(&[0f64, 0f64, 1f64]),
+			],
+			ManeuverFrame::Vnb => {
+				let v = unit(v);
+				let b = cross(&v, &n);
+				[v, n, b]
+			}
+			ManeuverFrame::Rtn => {
+				let r = unit(r);
+				let t = cross(&n, &r);
+				[r, t, n]
+			}
+		}
+	}
+}
+
+/// Instantaneous change of a body's velocity
+#[derive(Clone, Debug)]
+pub struct Maneuver {
+	pub et: f64,
+	pub body: i32,
+	pub frame: ManeuverFrame,
+	/// Delta-v in m/s
+	pub dv: [f64; 3],
+}
+
+impl Maneuver {
+	/// Parse a line of the form 'UTC_TIMESTAMP BODY J2000|VNB|RTN DV1 DV2 DV3', delta-v in m/s
+	pub fn parse(line: &str) -> Result<Self, String> {
+		let invalid = || {
+			format!(
+				"Invalid maneuver '{line}' - Expected UTC_TIMESTAMP BODY J2000|VNB|RTN DV1 DV2 DV3"
+			)
+		};
+
+		let fields = line.split_whitespace().collect::<Vec<_>>();
+		let (epoch, body, frame, dv) = match fields[..] {
+			[epoch, body, frame, dv1, dv2, dv3] => (epoch, body, frame, [dv1, dv2, dv3]),
+			_ => return Err(invalid()),
+		};
+
+		let frame = match frame.to_uppercase().as_str() {
+			"J2000" => ManeuverFrame::J2000,
+			"VNB" => ManeuverFrame::Vnb,
+			"RTN" => ManeuverFrame::Rtn,
+			_ => return Err(invalid()),
+		};
+
+		let mut parsed_dv = [0f64; 3];
+		for (p, v) in parsed_dv.iter_mut().zip(dv) {
+			*p = v.parse().map_err(|_| invalid())?;
+		}
+
+		Ok(Self {
+			et: spice::str2et(epoch),
+			body: spice_utils::naif_ids(&[body])?[0],
+			frame,
+			dv: parsed_dv,
+		})
+	}
+
+	/// Read maneuvers from a file with one maneuver per line, sorted by epoch.
+	/// Empty lines and lines starting with '#' are ignored
+	pub fn from_file(path: &str) -> Result<Vec<Self>, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("Could not read maneuver file '{path}': {e}"))?;
+
+		let mut maneuvers = contents
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(Self::parse)
+			.collect::<Result<Vec<_>, _>>()?;
+
+		maneuvers.sort_by(|a, b| a.et.partial_cmp(&b.et).unwrap());
+		Ok(maneuvers)
+	}
+
+	/// Delta-v in J2000, given the body's state relative to the observing body
+	pub fn dv_j2000(&self, rel_state: &Array1<f64>) -> Array1<f64> {
+		let dv = arr1(&self.dv);
+		if self.frame == ManeuverFrame::J2000 {
+			return dv;
+		}
+
+		let axes = self.frame.axes(rel_state);
+		dv[0] * &axes[0] + dv[1] * &axes[1] + dv[2] * &axes[2]
+	}
+
+	/// Delta-v in J2000 to undo when propagating backward, given the body's state relative to the
+	/// observing body after the maneuver. VNB and RTN axes are those of the state before it, which
+	/// is found by fixed-point iteration
+	pub fn undo_dv_j2000(&self, rel_state: &Array1<f64>) -> Result<Array1<f64>, String> {
+		let speed = rel_state
+			.slice(s![3..6])
+			.dot(&rel_state.slice(s![3..6]))
+			.sqrt();
+		let mut dv = self.dv_j2000(rel_state);
+		for _ in 0..100 {
+			let mut before = rel_state.clone();
+			before.slice_mut(s![3..6]).scaled_add(-1f64, &dv);
+			let next = self.dv_j2000(&before);
+			let change = &next - &dv;
+			dv = next;
+			if change.dot(&change).sqrt() <= 1e-12 * speed {
+				return Ok(dv);
+			}
+		}
+
+		Err(format!(
+			"Could not undo maneuver of body {} at ET {} - its delta-v is too large compared to the velocity",
+			self.body, self.et
+		))
+	}
+}
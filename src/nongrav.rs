@@ -0,0 +1,99 @@
+use crate::constants::AU;
+use crate::spice_utils;
+use ndarray::{arr1, s, Array1};
+
+/// Conversion factor from au/d^2 to m/s^2
+const AU_PER_DAY_SQ: f64 = AU / (86400f64 * 86400f64);
+
+/// Radial, transverse and normal acceleration parameters A1, A2, A3 in au/d^2
+pub type Parameters = [f64; 3];
+
+/// Dependence of the non-gravitational acceleration on heliocentric distance
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DistanceLaw {
+	/// Marsden-Sekanina water ice sublimation function g(r), normalized to 1 at 1 au
+	WaterSublimation,
+	/// (1 au / r)^2, as used for the transverse Yarkovsky acceleration of asteroids
+	InverseSquare,
+}
+
+impl DistanceLaw {
+	/// Scale factor of A1..A3 at heliocentric distance r in m
+	pub fn g(&self, r: f64) -> f64 {
+		match self {
+			Self::WaterSublimation => {
+				let (alpha, r0, m, n, k) = (0.1112620426, 2.808, 2.15, 5.093, 4.6142);
+				let x = r / AU / r0;
+				alpha * x.powf(-m) * (1f64 + x.powf(n)).powf(-k)
+			}
+			Self::InverseSquare => (AU / r).powi(2),
+		}
+	}
+}
+
+/// Non-gravitational acceleration parameters of a single small body
+pub struct NonGravBody {
+	/// Index of the body in the state vector
+	pub idx: usize,
+	/// Radial, transverse and normal acceleration at 1 au in m/s^2
+	pub a: [f64; 3],
+	pub law: DistanceLaw,
+}
+
+impl NonGravBody {
+	/// Cometary outgassing with the given parameters (A1, A2, A3 in au/d^2), or parameters
+	/// retrieved from the kernel pool (BODYnnn_A1, BODYnnn_A2, BODYnnn_A3)
+	pub fn comet(body: i32, idx: usize, params: Option<Parameters>) -> Result<Self, String> {
+		let params = match params {
+			Some(params) => params,
+			None => [
+				spice_utils::body_constants(body, "A1", 1)?[0],
+				spice_utils::body_constants(body, "A2", 1)?[0],
+				spice_utils::body_constants(body, "A3", 1)?[0],
+			],
+		};
+		Ok(Self {
+			idx,
+			a: params.map(|a| a * AU_PER_DAY_SQ),
+			law: DistanceLaw::WaterSublimation,
+		})
+	}
+
+	/// Transverse Yarkovsky acceleration with the given A2 in au/d^2, or A2 retrieved from the
+	/// kernel pool (BODYnnn_A2)
+	pub fn yarkovsky(body: i32, idx: usize, a2: Option<f64>) -> Result<Self, String> {
+		let a2 = match a2 {
+			Some(a2) => a2,
+			None => spice_utils::body_constants(body, "A2", 1)?[0],
+		};
+		Ok(Self {
+			idx,
+			a: [0f64, a2 * AU_PER_DAY_SQ, 0f64],
+			law: DistanceLaw::InverseSquare,
+		})
+	}
+
+	/// Acceleration in J2000, given the body's state relative to the Sun
+	pub fn acceleration(&self, helio_state: &Array1<f64>) -> Array1<f64> {
+		let r = helio_state.slice(s![..3]).to_owned();
+		let v = helio_state.slice(s![3..6]);
+		let r_norm = r.dot(&r).sqrt();
+
+		// Radial, transverse and normal unit vectors
+		let r_hat = &r / r_norm;
+		let h = arr1(&[
+			r[1] * v[2] - r[2] * v[1],
+			r[2] * v[0] - r[0] * v[2],
+			r[0] * v[1] - r[1] * v[0],
+		]);
+		let n_hat = &h / h.dot(&h).sqrt();
+		let t_hat = arr1(&[
+			n_hat[1] * r_hat[2] - n_hat[2] * r_hat[1],
+			n_hat[2] * r_hat[0] - n_hat[0] * r_hat[2],
+			n_hat[0] * r_hat[1] - n_hat[1] * r_hat[0],
+		]);
+
+		let g = self.law.g(r_norm);
+		g * (self.a[0] * r_hat + self.a[1] * t_hat + self.a[2] * n_hat)
+	}
+}
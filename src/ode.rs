@@ -1,52 +1,409 @@
+use crate::constants;
+use crate::drag;
+use crate::harmonics;
+use crate::nongrav;
+use crate::relativity;
 use crate::spice_utils;
-use ndarray::{s, Array1};
+use crate::srp;
+use crate::thrust;
+use crate::variational;
+use ndarray::{s, Array1, Array2};
 
-/// Calculate derivative of given state
+/// Number of state vector elements per body: position, velocity and mass. Mass is only tracked
+/// (non-zero) for small bodies with thrust arcs
+pub const STATE_SIZE: usize = 7;
+
+/// Parameters of the force model, resolved from SPICE prior to integration
+pub struct ForceModel {
+	/// Standard gravitational parameters of propagated bodies (0 for small bodies)
+	pub mus: Vec<f64>,
+	/// NAIF-IDs of bodies to pull states from SPICE for, and their standard gravitational parameters
+	pub attractors: Vec<(i32, f64)>,
+	/// Observing body; states are relative to it
+	pub cb_id: i32,
+	/// NAIF-IDs of propagated bodies, in state vector order
+	pub ids: Vec<i32>,
+	/// Oblate bodies whose zonal harmonics to consider
+	pub zonals: Vec<harmonics::ZonalHarmonics>,
+	/// Full spherical harmonic gravity field of a single body
+	pub gravity_field: Option<harmonics::GravityField>,
+	/// Solar radiation pressure acting on small bodies
+	pub srp: Option<srp::SrpModel>,
+	/// Atmospheric drag acting on small bodies
+	pub drag: Option<drag::DragModel>,
+	/// Non-gravitational accelerations of small bodies - cometary outgassing and Yarkovsky effect
+	pub nongrav: Vec<nongrav::NonGravBody>,
+	/// Post-Newtonian correction to gravitational accelerations
+	pub relativity: Option<relativity::RelativityModel>,
+	/// Thrust arcs of small bodies, with indices of the thrusting bodies in the state vector and
+	/// their dry masses in kg
+	pub thrust: Vec<(usize, f64, thrust::ThrustArc)>,
+	/// Indices of small bodies whose state transition matrices to propagate, in the order of their
+	/// variational blocks following the bodies' blocks in the state vector
+	pub stm: Vec<usize>,
+}
+
+impl ForceModel {
+	/// State of a propagated body, attractor or the observing body relative to the observing body
+	fn state_of(&self, body: i32, et: f64, state: &Array1<f64>) -> Result<Array1<f64>, String> {
+		if let Some(idx) = self.ids.iter().position(|&id| id == body) {
+			Ok(state
+				.slice(s![(idx * STATE_SIZE)..(idx * STATE_SIZE + 6)])
+				.to_owned())
+		} else if body == self.cb_id {
+			Ok(Array1::zeros(6))
+		} else {
+			spice_utils::state_at_instant(body, self.cb_id, et)
+		}
+	}
+
+	/// Position of a propagated body, attractor or the observing body relative to the observing body
+	fn position_of(&self, body: i32, et: f64, state: &Array1<f64>) -> Result<Array1<f64>, String> {
+		Ok(self.state_of(body, et, state)?.slice(s![..3]).to_owned())
+	}
+
+	/// Whether any accelerations other than the Newtonian gravity of point masses are modelled
+	fn has_perturbations(&self) -> bool {
+		!self.zonals.is_empty()
+			|| self.gravity_field.is_some()
+			|| self.srp.is_some()
+			|| self.drag.is_some()
+			|| !self.nongrav.is_empty()
+			|| self.relativity.is_some()
+			|| !self.thrust.is_empty()
+	}
+
+	/// Current mass of a small body, if it is tracked in the state vector
+	fn mass_of(&self, idx: usize, state: &Array1<f64>) -> Option<f64> {
+		Some(state[idx * STATE_SIZE + 6]).filter(|&m| m > 0f64)
+	}
+}
+
+/// Calculate derivative of given state. `thrusting` holds whether each of the model's thrust arcs is
+/// firing - it is decided once per integration arc so that solver stages evaluated exactly at the
+/// start or end of a thrust arc see the same thrust as the rest of the step
 pub fn n_body_ode(
 	et: f64,
 	state: &Array1<f64>,
-	mus: &[f64],
-	attractors: &[(i32, f64)],
-	cb_id: i32,
+	model: &ForceModel,
+	thrusting: &[bool],
 ) -> Result<Array1<f64>, String> {
+	let mus = &model.mus;
 	let n = mus.len();
 
-	let mut derivative = Array1::<f64>::zeros(n * 6);
+	// Any variational blocks follow those of the bodies
+	let mut derivative = Array1::<f64>::zeros(state.len());
 
 	for b1 in 0..n {
 		// Derivative also includes velocities (1st derivative of position, acceleration is 2nd)
 		// Extract them from the state vectors
-		let mut v_slice = derivative.slice_mut(s![(b1 * 6)..(b1 * 6 + 3)]);
-		v_slice += &state.slice(s![(b1 * 6 + 3)..(b1 * 6 + 6)]);
+		let mut v_slice = derivative.slice_mut(s![(b1 * STATE_SIZE)..(b1 * STATE_SIZE + 3)]);
+		v_slice += &state.slice(s![(b1 * STATE_SIZE + 3)..(b1 * STATE_SIZE + 6)]);
 
 		// Calculate accelerations
 		for b2 in (b1 + 1)..n {
 			// Vector from b1 to b2
-			let r =
-				&state.slice(s![(b2 * 6)..(b2 * 6 + 3)]) - &state.slice(s![(b1 * 6)..(b1 * 6 + 3)]);
+			let r = &state.slice(s![(b2 * STATE_SIZE)..(b2 * STATE_SIZE + 3)])
+				- &state.slice(s![(b1 * STATE_SIZE)..(b1 * STATE_SIZE + 3)]);
 			// Calc accelerations
 			let r_norm_cubed = r.dot(&r).sqrt().powf(3.0);
 			let (a1, a2) = (mus[b2] / r_norm_cubed * &r, mus[b1] / r_norm_cubed * -(&r));
 			// Update total acceleration for each body
-			let mut a1_slice = derivative.slice_mut(s![(b1 * 6 + 3)..(b1 * 6 + 6)]);
+			let mut a1_slice =
+				derivative.slice_mut(s![(b1 * STATE_SIZE + 3)..(b1 * STATE_SIZE + 6)]);
 			a1_slice += &a1;
-			let mut a2_slice = derivative.slice_mut(s![(b2 * 6 + 3)..(b2 * 6 + 6)]);
+			let mut a2_slice =
+				derivative.slice_mut(s![(b2 * STATE_SIZE + 3)..(b2 * STATE_SIZE + 6)]);
 			a2_slice += &a2;
 		}
 	}
 
-	for &(attractor_id, mu) in attractors {
-		let attractor_pos = spice_utils::state_at_instant(attractor_id, cb_id, et)?
+	for &(attractor_id, mu) in &model.attractors {
+		let attractor_pos = spice_utils::state_at_instant(attractor_id, model.cb_id, et)?
 			.slice(s![..3])
 			.to_owned();
 		for b in 0..n {
-			let pos_slice = state.slice(s![(b * 6)..(b * 6 + 3)]);
-			let mut a_slice = derivative.slice_mut(s![(b * 6 + 3)..(b * 6 + 6)]);
+			let pos_slice = state.slice(s![(b * STATE_SIZE)..(b * STATE_SIZE + 3)]);
+			let mut a_slice = derivative.slice_mut(s![(b * STATE_SIZE + 3)..(b * STATE_SIZE + 6)]);
 
 			let r = &attractor_pos - &pos_slice;
 			let a = (mu / r.dot(&r).sqrt().powf(3.0)) * &r;
 			a_slice += &a;
 		}
 	}
+
+	add_perturbations(&mut derivative, et, state, model, thrusting)?;
+
+	if !model.stm.is_empty() {
+		add_variational_equations(&mut derivative, et, state, model, thrusting)?;
+	}
+
 	Ok(derivative)
 }
+
+/// Add accelerations other than the Newtonian gravity of point masses
+fn add_perturbations(
+	derivative: &mut Array1<f64>,
+	et: f64,
+	state: &Array1<f64>,
+	model: &ForceModel,
+	thrusting: &[bool],
+) -> Result<(), String> {
+	let n = model.mus.len();
+
+	for zonal in &model.zonals {
+		let pole = zonal.pole(et)?;
+		add_field_accelerations(derivative, state, model, zonal.body, zonal.mu, et, |r| {
+			zonal.acceleration(r, &pole)
+		})?;
+	}
+
+	if let Some(field) = &model.gravity_field {
+		let rot = spice_utils::rotation("J2000", &field.frame, et)?;
+		add_field_accelerations(derivative, state, model, field.body, field.mu, et, |r| {
+			field.acceleration(r, &rot)
+		})?;
+	}
+
+	if let Some(srp_model) = &model.srp {
+		let sun_pos = model.position_of(constants::SUN, et, state)?;
+
+		// Positions of occulting bodies, retrieved once for all small bodies
+		let mut occulters = Vec::new();
+		if srp_model.shadow.is_some() {
+			for &(id, radius) in &srp_model.occulters {
+				occulters.push((model.position_of(id, et, state)?, radius));
+			}
+		}
+
+		for sc in &srp_model.spacecraft {
+			let pos = state.slice(s![(sc.idx * STATE_SIZE)..(sc.idx * STATE_SIZE + 3)]);
+			let to_sun = &sun_pos - &pos;
+
+			let illumination = match srp_model.shadow {
+				Some(shadow) => occulters
+					.iter()
+					.map(|(occ_pos, radius)| {
+						srp::illumination(
+							shadow,
+							&to_sun,
+							&(occ_pos - &pos),
+							srp_model.sun_radius,
+							*radius,
+						)
+					})
+					.fold(1f64, f64::min),
+				None => 1f64,
+			};
+
+			let mut a_slice =
+				derivative.slice_mut(s![(sc.idx * STATE_SIZE + 3)..(sc.idx * STATE_SIZE + 6)]);
+			let mass = model.mass_of(sc.idx, state).unwrap_or(sc.mass);
+			a_slice += &srp::acceleration(sc, mass, &-to_sun, illumination);
+		}
+	}
+
+	if let Some(drag_model) = &model.drag {
+		let body_state = model.state_of(drag_model.body, et, state)?;
+		let xform = spice_utils::state_transformation("J2000", &drag_model.frame, et)?;
+
+		for sc in &drag_model.spacecraft {
+			let rel_state =
+				&state.slice(s![(sc.idx * STATE_SIZE)..(sc.idx * STATE_SIZE + 6)]) - &body_state;
+
+			let mut a_slice =
+				derivative.slice_mut(s![(sc.idx * STATE_SIZE + 3)..(sc.idx * STATE_SIZE + 6)]);
+			let mass = model.mass_of(sc.idx, state).unwrap_or(sc.mass);
+			a_slice += &drag_model.acceleration(sc, mass, &rel_state, &xform);
+		}
+	}
+
+	if !model.nongrav.is_empty() {
+		let sun_state = model.state_of(constants::SUN, et, state)?;
+		for body in &model.nongrav {
+			let helio_state =
+				&state.slice(s![(body.idx * STATE_SIZE)..(body.idx * STATE_SIZE + 6)]) - &sun_state;
+
+			let mut a_slice =
+				derivative.slice_mut(s![(body.idx * STATE_SIZE + 3)..(body.idx * STATE_SIZE + 6)]);
+			a_slice += &body.acceleration(&helio_state);
+		}
+	}
+
+	match &model.relativity {
+		Some(relativity::RelativityModel::Schwarzschild { body, mu }) => {
+			let body_state = model.state_of(*body, et, state)?;
+			for b in (0..n).filter(|&b| model.ids[b] != *body) {
+				let rel_state =
+					&state.slice(s![(b * STATE_SIZE)..(b * STATE_SIZE + 6)]) - &body_state;
+				let a = relativity::schwarzschild(
+					*mu,
+					&rel_state.slice(s![..3]).to_owned(),
+					&rel_state.slice(s![3..]).to_owned(),
+				);
+
+				let mut a_slice =
+					derivative.slice_mut(s![(b * STATE_SIZE + 3)..(b * STATE_SIZE + 6)]);
+				a_slice += &a;
+			}
+		}
+		Some(relativity::RelativityModel::Eih) => {
+			// Propagated bodies first, followed by attractors
+			let mut pos = Vec::with_capacity(n + model.attractors.len());
+			let mut vel = Vec::with_capacity(n + model.attractors.len());
+			let mut all_mus = model.mus.clone();
+			for b in 0..n {
+				pos.push(
+					state
+						.slice(s![(b * STATE_SIZE)..(b * STATE_SIZE + 3)])
+						.to_owned(),
+				);
+				vel.push(
+					state
+						.slice(s![(b * STATE_SIZE + 3)..(b * STATE_SIZE + 6)])
+						.to_owned(),
+				);
+			}
+			for &(id, mu) in &model.attractors {
+				let attractor_state = model.state_of(id, et, state)?;
+				pos.push(attractor_state.slice(s![..3]).to_owned());
+				vel.push(attractor_state.slice(s![3..]).to_owned());
+				all_mus.push(mu);
+			}
+
+			for (b, a) in relativity::eih(&pos, &vel, &all_mus, n).iter().enumerate() {
+				let mut a_slice =
+					derivative.slice_mut(s![(b * STATE_SIZE + 3)..(b * STATE_SIZE + 6)]);
+				a_slice += a;
+			}
+		}
+		None => {}
+	}
+
+	let active_arcs = model
+		.thrust
+		.iter()
+		.zip(thrusting)
+		.filter(|(_, &active)| active);
+	for ((idx, dry_mass, arc), _) in active_arcs {
+		let mass = model
+			.mass_of(*idx, state)
+			// Burns that use up the propellant end at the dry mass, up to rounding
+			.filter(|mass| *mass > dry_mass * (1f64 - 1e-9))
+			.ok_or_else(|| format!("Body {} ran out of propellant at ET {et}", arc.body))?;
+		let rel_state =
+			model.state_of(arc.body, et, state)? - model.state_of(model.cb_id, et, state)?;
+		let (a, mass_flow) = arc.acceleration(et, &rel_state, mass);
+
+		let mut a_slice = derivative.slice_mut(s![(idx * STATE_SIZE + 3)..(idx * STATE_SIZE + 6)]);
+		a_slice += &a;
+		derivative[idx * STATE_SIZE + 6] += mass_flow;
+	}
+
+	Ok(())
+}
+
+/// Add the derivatives of the variational blocks of small bodies. The partial derivatives of their
+/// accelerations with respect to position are analytic for the gravity of point masses, while
+/// those of all other accelerations are approximated by central differences. Small bodies don't
+/// affect each other, so all of them are varied at once
+fn add_variational_equations(
+	derivative: &mut Array1<f64>,
+	et: f64,
+	state: &Array1<f64>,
+	model: &ForceModel,
+	thrusting: &[bool],
+) -> Result<(), String> {
+	let n = model.mus.len();
+
+	// Partial derivatives of the accelerations with respect to position and velocity
+	let mut partials = vec![(Array2::zeros((3, 3)), Array2::zeros((3, 3))); model.stm.len()];
+
+	for (k, &idx) in model.stm.iter().enumerate() {
+		let pos = state.slice(s![(idx * STATE_SIZE)..(idx * STATE_SIZE + 3)]);
+		for b in (0..n).filter(|&b| b != idx && model.mus[b] > 0f64) {
+			let r = &state.slice(s![(b * STATE_SIZE)..(b * STATE_SIZE + 3)]) - &pos;
+			partials[k].0 += &variational::gravity_gradient(model.mus[b], &r);
+		}
+		for &(attractor_id, mu) in &model.attractors {
+			let r = model.position_of(attractor_id, et, state)? - pos;
+			partials[k].0 += &variational::gravity_gradient(mu, &r);
+		}
+	}
+
+	if model.has_perturbations() {
+		let bodies = state.slice(s![..(n * STATE_SIZE)]).to_owned();
+		for j in 0..6 {
+			let step = if j < 3 {
+				variational::POSITION_STEP
+			} else {
+				variational::VELOCITY_STEP
+			};
+
+			let (mut plus, mut minus) = (bodies.clone(), bodies.clone());
+			for &idx in &model.stm {
+				plus[idx * STATE_SIZE + j] += step;
+				minus[idx * STATE_SIZE + j] -= step;
+			}
+			let mut d_plus = Array1::zeros(bodies.len());
+			let mut d_minus = Array1::zeros(bodies.len());
+			add_perturbations(&mut d_plus, et, &plus, model, thrusting)?;
+			add_perturbations(&mut d_minus, et, &minus, model, thrusting)?;
+
+			for (k, &idx) in model.stm.iter().enumerate() {
+				let da = (&d_plus.slice(s![(idx * STATE_SIZE + 3)..(idx * STATE_SIZE + 6)])
+					- &d_minus.slice(s![(idx * STATE_SIZE + 3)..(idx * STATE_SIZE + 6)]))
+					/ (2f64 * step);
+				let mut column = if j < 3 {
+					partials[k].0.column_mut(j)
+				} else {
+					partials[k].1.column_mut(j - 3)
+				};
+				column += &da;
+			}
+		}
+	}
+
+	for (k, (a, b)) in partials.iter().enumerate() {
+		let start = (n + k * variational::STM_BLOCKS) * STATE_SIZE;
+		let end = start + variational::STM_BLOCKS * STATE_SIZE;
+		derivative
+			.slice_mut(s![start..end])
+			.assign(&variational::derivative(&state.slice(s![start..end]), a, b));
+	}
+
+	Ok(())
+}
+
+/// Add accelerations due to the non-spherical part of a body's gravity field to all other propagated
+/// bodies, and the corresponding reaction to the body itself if it is being propagated
+fn add_field_accelerations(
+	derivative: &mut Array1<f64>,
+	state: &Array1<f64>,
+	model: &ForceModel,
+	body: i32,
+	mu: f64,
+	et: f64,
+	acceleration: impl Fn(&Array1<f64>) -> Array1<f64>,
+) -> Result<(), String> {
+	let body_pos = model.position_of(body, et, state)?;
+	let body_idx = model.ids.iter().position(|&id| id == body);
+
+	let mut reaction = Array1::<f64>::zeros(3);
+
+	for b in (0..model.mus.len()).filter(|&b| Some(b) != body_idx) {
+		let r = &state.slice(s![(b * STATE_SIZE)..(b * STATE_SIZE + 3)]) - &body_pos;
+		let a = acceleration(&r);
+		reaction -= &(model.mus[b] / mu * &a);
+
+		let mut a_slice = derivative.slice_mut(s![(b * STATE_SIZE + 3)..(b * STATE_SIZE + 6)]);
+		a_slice += &a;
+	}
+
+	if let Some(idx) = body_idx {
+		let mut a_slice = derivative.slice_mut(s![(idx * STATE_SIZE + 3)..(idx * STATE_SIZE + 6)]);
+		a_slice += &reaction;
+	}
+
+	Ok(())
+}
@@ -1,15 +1,140 @@
-use crate::ode;
+use crate::constants;
+use crate::drag;
+use crate::events;
+use crate::harmonics;
+use crate::maneuvers;
+use crate::nongrav;
+use crate::ode::{self, STATE_SIZE};
+use crate::relativity;
 use crate::solvers;
 use crate::spice_utils;
-use ndarray::Array1;
+use crate::srp;
+use crate::thrust;
+use crate::variational;
+use ndarray::{s, Array1};
+use std::cell::RefCell;
 
+#[derive(Clone, Copy)]
 pub enum SolverConfig {
-	Rk4 { h: f64 },
-	Euler { h: f64 },
-	Dopri45 { h: f64, atol: f64, rtol: f64 },
+	Rk4 {
+		h: f64,
+	},
+	Euler {
+		h: f64,
+	},
+	Dopri45 {
+		h: f64,
+		atol: f64,
+		rtol: f64,
+		control: solvers::StepControl,
+	},
+	Dop853 {
+		h: f64,
+		atol: f64,
+		rtol: f64,
+		control: solvers::StepControl,
+	},
+	Rkf78 {
+		h: f64,
+		atol: f64,
+		rtol: f64,
+		control: solvers::StepControl,
+	},
+	Abm {
+		h: f64,
+		atol: f64,
+		rtol: f64,
+		control: solvers::StepControl,
+	},
+	GaussJackson {
+		h: f64,
+	},
+	Symplectic {
+		h: f64,
+		composition: solvers::Composition,
+	},
+	WisdomHolman {
+		h: f64,
+	},
+	Ias15 {
+		h: f64,
+		control: solvers::StepControl,
+	},
+	BulirschStoer {
+		h: f64,
+		atol: f64,
+		rtol: f64,
+		control: solvers::StepControl,
+	},
 }
 
-/// Propagate trajectories
+/// Perturbations to consider in addition to point-mass gravity
+#[derive(Default)]
+pub struct ForceModelConfig {
+	/// Bodies whose zonal harmonics to consider
+	pub zonal_bodies: Vec<i32>,
+	/// Maximum degree of zonal harmonics
+	pub zonal_degree: usize,
+	/// Body whose full gravity field to consider, coefficient file and optional truncation degree
+	pub gravity_field: Option<(i32, String, Option<usize>)>,
+	/// Small bodies subject to solar radiation pressure, with optional (area, mass, Cr)
+	pub srp: Vec<(i32, Option<srp::Parameters>)>,
+	/// Shadow model for solar radiation pressure
+	pub shadow: Option<srp::ShadowModel>,
+	/// Atmospheric drag
+	pub drag: Option<DragConfig>,
+	/// Small bodies subject to Marsden-Sekanina cometary forces, with optional (A1, A2, A3)
+	pub comet_forces: Vec<(i32, Option<nongrav::Parameters>)>,
+	/// Small bodies subject to the transverse Yarkovsky effect, with optional A2
+	pub yarkovsky: Vec<(i32, Option<f64>)>,
+	/// Post-Newtonian correction
+	pub relativity: Option<RelativityConfig>,
+	/// Finite burns of small bodies
+	pub thrust: Vec<thrust::ThrustArc>,
+}
+
+pub enum RelativityConfig {
+	/// Schwarzschild term of the observing body
+	Schwarzschild,
+	/// Full Einstein-Infeld-Hoffmann equations
+	Eih,
+}
+
+pub struct DragConfig {
+	/// Small bodies subject to drag, with optional (area, mass, Cd)
+	pub bodies: Vec<(i32, Option<drag::Parameters>)>,
+	/// Body whose atmosphere to consider
+	pub atmosphere_body: i32,
+	pub density: Box<dyn drag::DensityModel>,
+	/// Altitude in m above which drag is neglected
+	pub max_altitude: f64,
+}
+
+/// Continuous arc of propagated states between two maneuvers or thrust arc boundaries
+pub struct Segment {
+	pub ets: Vec<f64>,
+	pub states: Vec<Array1<f64>>,
+}
+
+/// Index of a small body in the state vector
+fn small_body_idx(
+	bodies: &[i32],
+	small_bodies: &[i32],
+	id: i32,
+	force: &str,
+) -> Result<usize, String> {
+	match small_bodies.iter().position(|&b| b == id) {
+		Some(idx) => Ok(bodies.len() + idx),
+		None => Err(format!(
+			"{force} can only be applied to small bodies, {id} is not one"
+		)),
+	}
+}
+
+/// Propagate trajectories. Returns the propagated segments and the events that occurred. The
+/// state transition matrices of stm_bodies are propagated alongside, and can be read from the
+/// states of the segments with variational::stm
+#[allow(clippy::too_many_arguments)]
 pub fn propagate(
 	bodies: &[i32],
 	small_bodies: &[i32],
@@ -18,7 +143,12 @@ pub fn propagate(
 	t0: &str,
 	tfinal: &str,
 	solver: SolverConfig,
-) -> Result<(Vec<Array1<f64>>, Vec<f64>), String> {
+	output_step: Option<f64>,
+	forces: ForceModelConfig,
+	maneuvers: &[maneuvers::Maneuver],
+	events: &[events::Event],
+	stm_bodies: &[i32],
+) -> Result<(Vec<Segment>, Vec<events::Occurrence>), String> {
 	println!(
 		"Propagating trajectories of {} bodies from {} to {}",
 		bodies.len() + small_bodies.len(),
@@ -28,20 +158,142 @@ pub fn propagate(
 
 	let et0 = spice::str2et(t0);
 	let etfinal = spice::str2et(tfinal);
-	if et0 >= etfinal {
-		return Err("Start time is greater than end time".to_string());
+	if et0 == etfinal {
+		return Err("Start and end time must differ".to_string());
 	}
+	// Propagate backward in time if the end time precedes the start time
+	let backward = etfinal < et0;
+	// Whether an epoch lies within the propagation interval, excluding its end
+	let within = |et: f64| {
+		if backward {
+			et <= et0 && et > etfinal
+		} else {
+			et >= et0 && et < etfinal
+		}
+	};
+
+	let ids = bodies
+		.iter()
+		.cloned()
+		.chain(small_bodies.iter().cloned())
+		.collect::<Vec<_>>();
 
 	// Initial conditions - retrieve state vectors from SPICE
-	let y0 = spice_utils::states_at_instant(
-		&bodies
-			.iter()
-			.cloned()
-			.chain(small_bodies.iter().cloned())
-			.collect::<Vec<_>>(),
-		cb_id,
-		et0,
-	)?;
+	let mut y = spice_utils::states_at_instant(&ids, cb_id, et0, STATE_SIZE)?;
+
+	// Resolve state vector indices of maneuvering bodies
+	let mut burns = Vec::with_capacity(maneuvers.len());
+	for maneuver in maneuvers {
+		if !within(maneuver.et) {
+			return Err(format!(
+				"Maneuver of body {} at ET {} is outside the propagation interval",
+				maneuver.body, maneuver.et
+			));
+		}
+		let idx = small_body_idx(bodies, small_bodies, maneuver.body, "Maneuvers")?;
+		burns.push((maneuver, idx));
+	}
+	let cb_idx = bodies.iter().position(|&id| id == cb_id);
+
+	// Resolve event functions, and state vector indices of bodies maneuvering at events
+	let mut detectors = Vec::with_capacity(events.len());
+	for event in events {
+		let idx = match event.action {
+			events::Action::Maneuver(..) => Some(small_body_idx(
+				bodies,
+				small_bodies,
+				event.body,
+				"Event maneuvers",
+			)?),
+			_ => None,
+		};
+		detectors.push((events::Detector::new(event, &ids, cb_id)?, idx));
+	}
+
+	// Masses given along with solar radiation pressure or drag parameters
+	let given_masses = forces
+		.srp
+		.iter()
+		.chain(forces.drag.iter().flat_map(|drag| &drag.bodies))
+		.filter_map(|&(id, params)| params.map(|[_, mass, _]| (id, mass)))
+		.collect::<Vec<_>>();
+
+	// Resolve state vector indices and dry masses of thrusting bodies, and start tracking their
+	// mass. Initial masses are those given for other forces, or BODYnnn_MASS otherwise
+	let mut thrust = Vec::with_capacity(forces.thrust.len());
+	// Propellant left of each thrusting body after the arcs so far, in chronological order
+	let mut propellant: Vec<(i32, f64)> = Vec::new();
+	let mut arcs = forces.thrust;
+	arcs.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+	for mut arc in arcs {
+		let idx = small_body_idx(bodies, small_bodies, arc.body, "Thrust")?;
+		let mass = match given_masses.iter().find(|&&(id, _)| id == arc.body) {
+			Some(&(_, mass)) => mass,
+			None => spice_utils::body_constants(arc.body, "MASS", 1)?[0],
+		};
+		let dry_mass = spice_utils::body_constants(arc.body, "DRY_MASS", 1)?[0];
+		if !(dry_mass > 0f64 && dry_mass < mass) {
+			return Err(format!(
+				"Dry mass of body {} must be positive and less than its initial mass {mass} kg",
+				arc.body
+			));
+		}
+		y[idx * STATE_SIZE + 6] = mass;
+
+		// Going forward, a burn ends once the body's propellant is used up, and the integration
+		// arc is split there as at the end of a thrust arc. The mass flow of each arc is constant
+		if !backward {
+			let k = match propellant.iter().position(|&(id, _)| id == arc.body) {
+				Some(k) => k,
+				None => {
+					propellant.push((arc.body, mass - dry_mass));
+					propellant.len() - 1
+				}
+			};
+			let (start, stop) = (arc.start.max(et0), arc.stop.min(etfinal));
+			if stop > start {
+				let burnt = -arc.mass_flow() * (stop - start);
+				let left = &mut propellant[k].1;
+				if burnt > *left {
+					arc.stop = start + *left / -arc.mass_flow();
+					*left = 0f64;
+				} else {
+					*left -= burnt;
+				}
+			}
+		}
+		thrust.push((idx, dry_mass, arc));
+	}
+
+	// Resolve state vector indices of bodies whose state transition matrices to propagate, and
+	// append their variational blocks to the state vector
+	let mut stm = Vec::with_capacity(stm_bodies.len());
+	for &id in stm_bodies {
+		stm.push(small_body_idx(
+			bodies,
+			small_bodies,
+			id,
+			"Variational equations",
+		)?);
+	}
+	if !stm.is_empty() && matches!(solver, SolverConfig::WisdomHolman { .. }) {
+		return Err("Variational equations cannot be integrated with --method wisdom-holman - Its Kepler drifts only apply to bodies".to_string());
+	}
+	y = y
+		.iter()
+		.cloned()
+		.chain(stm.iter().flat_map(|_| variational::identity()))
+		.collect::<Array1<_>>();
+
+	// Epochs at which to stop and restart the solver
+	let mut breaks = maneuvers
+		.iter()
+		.map(|m| m.et)
+		.chain(thrust.iter().flat_map(|(_, _, arc)| [arc.start, arc.stop]))
+		.filter(|&et| et != et0 && within(et))
+		.collect::<Vec<_>>();
+	breaks.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	breaks.dedup();
 
 	// Retrieve standard gravitational parameters from SPICE
 	let mut mus = vec![0f64; bodies.len() + small_bodies.len()];
@@ -55,28 +307,466 @@ pub fn propagate(
 		attractors_with_mus.push((id, spice_utils::mu(id)?));
 	}
 
-	// The actual derivative being integrated. Returns rate of change of system state
-	let f =
-		move |et: f64, y: &Array1<f64>| ode::n_body_ode(et, y, &mus, &attractors_with_mus, cb_id);
+	// Retrieve zonal harmonics and pole orientation data for oblate bodies
+	if !forces.zonal_bodies.is_empty() && forces.zonal_degree < 2 {
+		return Err(format!(
+			"Maximum degree of zonal harmonics must be at least 2, got {}",
+			forces.zonal_degree
+		));
+	}
+	let mut zonals = Vec::with_capacity(forces.zonal_bodies.len());
+	for &id in &forces.zonal_bodies {
+		zonals.push(harmonics::ZonalHarmonics::from_spice(
+			id,
+			forces.zonal_degree,
+		)?);
+	}
+
+	let gravity_field = match forces.gravity_field {
+		Some((id, path, degree)) => Some(harmonics::GravityField::from_file(&path, id, degree)?),
+		None => None,
+	};
+
+	let srp = if forces.srp.is_empty() {
+		None
+	} else {
+		let mut spacecraft = Vec::with_capacity(forces.srp.len());
+		for &(id, params) in &forces.srp {
+			let idx = small_body_idx(bodies, small_bodies, id, "Solar radiation pressure")?;
+			spacecraft.push(srp::Spacecraft::new(id, idx, params)?);
+		}
 
-	// Create solver object based on config on the heap (since exact type is unknown)
-	let mut solver: Box<dyn solvers::Solver> = match solver {
-		SolverConfig::Rk4 { h } => Box::new(solvers::Rk4::new(f, h, et0, &y0, etfinal)),
-		SolverConfig::Euler { h } => Box::new(solvers::Euler::new(f, h, et0, &y0, etfinal)),
-		SolverConfig::Dopri45 { h, atol, rtol } => {
-			Box::new(solvers::Dopri45::new(f, h, et0, &y0, etfinal, atol, rtol))
+		// Any large body or attractor whose radius is known may cast a shadow
+		let mut occulters = Vec::new();
+		if forces.shadow.is_some() {
+			for &id in bodies.iter().chain(attractors) {
+				if id != constants::SUN && spice_utils::has_body_constant(id, "RADII") {
+					occulters.push((
+						id,
+						spice_utils::body_constants(id, "RADII", 3)?[0] * 1000f64,
+					));
+				}
+			}
 		}
+
+		Some(srp::SrpModel {
+			spacecraft,
+			shadow: forces.shadow,
+			sun_radius: spice_utils::body_constants(constants::SUN, "RADII", 3)?[0] * 1000f64,
+			occulters,
+		})
 	};
 
-	// TODO: Approximate number of required steps to avoid reallocations
-	let mut ets = Vec::new();
-	let mut states = Vec::new();
+	let drag = match forces.drag {
+		Some(config) => {
+			let mut spacecraft = Vec::with_capacity(config.bodies.len());
+			for &(id, params) in &config.bodies {
+				let idx = small_body_idx(bodies, small_bodies, id, "Atmospheric drag")?;
+				spacecraft.push(drag::Spacecraft::new(id, idx, params)?);
+			}
 
-	// Collect integral points
-	while let Some((et, state)) = solver.next_state()? {
-		ets.push(et);
-		states.push(state);
+			let radii = spice_utils::body_constants(config.atmosphere_body, "RADII", 3)?;
+
+			Some(drag::DragModel {
+				body: config.atmosphere_body,
+				frame: spice_utils::body_fixed_frame(config.atmosphere_body)?,
+				radii: (radii[0] * 1000f64, radii[2] * 1000f64),
+				density: config.density,
+				max_altitude: config.max_altitude,
+				spacecraft,
+			})
+		}
+		None => None,
+	};
+
+	let mut nongrav = Vec::with_capacity(forces.comet_forces.len() + forces.yarkovsky.len());
+	for &(id, params) in &forces.comet_forces {
+		let idx = small_body_idx(bodies, small_bodies, id, "Cometary forces")?;
+		nongrav.push(nongrav::NonGravBody::comet(id, idx, params)?);
+	}
+	for &(id, a2) in &forces.yarkovsky {
+		let idx = small_body_idx(bodies, small_bodies, id, "Yarkovsky effect")?;
+		nongrav.push(nongrav::NonGravBody::yarkovsky(id, idx, a2)?);
 	}
 
-	Ok((states, ets))
+	let relativity = match forces.relativity {
+		Some(RelativityConfig::Schwarzschild) => Some(relativity::RelativityModel::Schwarzschild {
+			body: cb_id,
+			mu: spice_utils::mu(cb_id)?,
+		}),
+		Some(RelativityConfig::Eih) => Some(relativity::RelativityModel::Eih),
+		None => None,
+	};
+
+	let model = ode::ForceModel {
+		mus,
+		attractors: attractors_with_mus,
+		cb_id,
+		ids,
+		zonals,
+		gravity_field,
+		srp,
+		drag,
+		nongrav,
+		relativity,
+		thrust,
+		stm,
+	};
+
+	// Thrust arcs never begin or end within an integration arc, so their state at its midpoint
+	// holds throughout
+	let model = &model;
+	let derivatives = |midpoint: f64| {
+		let thrusting = model
+			.thrust
+			.iter()
+			.map(|(_, _, arc)| arc.is_active(midpoint))
+			.collect::<Vec<_>>();
+		move |et: f64, y: &Array1<f64>| ode::n_body_ode(et, y, model, &thrusting)
+	};
+
+	propagate_arcs(
+		derivatives,
+		solver,
+		(et0, etfinal),
+		y,
+		&model.mus,
+		output_step,
+		burns,
+		breaks,
+		cb_idx,
+		&detectors,
+	)
+}
+
+/// Propagate the state y from et0 to etfinal, arc by arc. The solver stops at each break epoch
+/// and at each event that stops the propagation or triggers a maneuver. burns are the scheduled
+/// maneuvers with the state vector indices of their bodies, and detectors the event functions with
+/// those of bodies maneuvering at events. burns and breaks are in chronological order.
+/// derivatives returns the derivative to integrate over an arc, given the arc's midpoint. States
+/// are kept at the solver's steps, or at the epochs et0 +/- k * output_step and the arc boundaries
+#[allow(clippy::too_many_arguments)]
+pub fn propagate_arcs<D, F>(
+	derivatives: D,
+	solver: SolverConfig,
+	(et0, etfinal): (f64, f64),
+	mut y: Array1<f64>,
+	mus: &[f64],
+	output_step: Option<f64>,
+	mut burns: Vec<(&maneuvers::Maneuver, usize)>,
+	mut breaks: Vec<f64>,
+	cb_idx: Option<usize>,
+	detectors: &[(events::Detector, Option<usize>)],
+) -> Result<(Vec<Segment>, Vec<events::Occurrence>), String>
+where
+	D: Fn(f64) -> F,
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	let backward = etfinal < et0;
+	if backward {
+		burns.reverse();
+		breaks.reverse();
+	}
+
+	let mut segments = Vec::new();
+	let mut occurrences = Vec::new();
+	let mut et = et0;
+	let mut burns = burns.into_iter().peekable();
+	let mut breaks = breaks.into_iter();
+	let mut arc_end = breaks.next().unwrap_or(etfinal);
+
+	// Propagate arc by arc, stopping the solver at each maneuver epoch and thrust arc boundary, and
+	// at events that stop the propagation or trigger maneuvers
+	loop {
+		// Apply all maneuvers scheduled for the current epoch. Going backward, they are undone
+		while let Some((maneuver, idx)) =
+			burns.next_if(|(m, _)| if backward { m.et >= et } else { m.et <= et })
+		{
+			let mut rel_state = y
+				.slice(s![(idx * STATE_SIZE)..(idx * STATE_SIZE + 6)])
+				.to_owned();
+			if let Some(cb_idx) = cb_idx {
+				rel_state -= &y.slice(s![(cb_idx * STATE_SIZE)..(cb_idx * STATE_SIZE + 6)]);
+			}
+			let dv = if backward {
+				maneuver.undo_dv_j2000(&rel_state)?
+			} else {
+				maneuver.dv_j2000(&rel_state)
+			};
+			y.slice_mut(s![(idx * STATE_SIZE + 3)..(idx * STATE_SIZE + 6)])
+				.scaled_add(if backward { -1f64 } else { 1f64 }, &dv);
+		}
+
+		// The actual derivative being integrated. Returns rate of change of system state
+		let f = derivatives(0.5 * (et + arc_end));
+
+		// Include the initial state so that consecutive segments meet at the arc boundary
+		let mut ets = vec![et];
+		let mut states = vec![y.clone()];
+
+		// Events are detected on the solver's own steps, which are also interpolated to the output
+		// grid if there is one
+		let mut solver = build_solver(solver, &f, et, &y, arc_end, mus, None);
+		let grid_step = output_step.map(|step| if backward { -step } else { step });
+		let mut last = (et, y.clone());
+		let mut values = detectors
+			.iter()
+			.map(|(detector, _)| detector.value(et, &y))
+			.collect::<Result<Vec<_>, _>>()?;
+		// Event ending the arc early
+		let mut interruption = None;
+		while let Some((next_et, state)) = solver.next_state()? {
+			let next_values = detectors
+				.iter()
+				.map(|(detector, _)| detector.value(next_et, &state))
+				.collect::<Result<Vec<_>, _>>()?;
+
+			// Solvers without a continuous extension are interpolated from the derivatives, which
+			// are evaluated once they are needed
+			let derivatives = RefCell::new(None);
+			let interpolate = |x: f64| {
+				if let Some(y) = solver.interpolate(x) {
+					return Ok(y);
+				}
+				let mut derivatives = derivatives.borrow_mut();
+				if derivatives.is_none() {
+					*derivatives = Some((f(last.0, &last.1)?, f(next_et, &state)?));
+				}
+				let (dy0, dy1) = derivatives.as_ref().unwrap();
+				Ok(solvers::hermite(
+					(last.0, &last.1, dy0),
+					(next_et, &state, dy1),
+					x,
+					STATE_SIZE,
+				))
+			};
+
+			let triggered = detectors.iter().enumerate().any(|(i, (detector, _))| {
+				detector.triggered((last.0, values[i]), (next_et, next_values[i]))
+			});
+			if triggered {
+				let found = events::scan(
+					detectors.iter().map(|(detector, _)| detector),
+					(last.0, &values),
+					(next_et, &next_values),
+					interpolate,
+				)?;
+				for (event_et, i) in found {
+					let detector = &detectors[i].0;
+					let event_state = interpolate(event_et)?;
+					occurrences.push(events::Occurrence {
+						et: event_et,
+						event: i,
+						relative_state: detector.relative_state(event_et, &event_state)?,
+					});
+					if detector.event.action != events::Action::Record {
+						interruption = Some((event_et, event_state, i));
+						break;
+					}
+				}
+			}
+
+			// Keep the solver's steps, or the grid epochs within them. The state at the end of the
+			// arc is always kept
+			let step_end = match &interruption {
+				Some((event_et, _, _)) => *event_et,
+				None => next_et,
+			};
+			let mut keep_end = true;
+			if let Some(step) = grid_step {
+				let grid = grid_epochs(et0, step, last.0, step_end);
+				for &x in grid.iter().filter(|&&x| x != step_end) {
+					ets.push(x);
+					states.push(interpolate(x)?);
+				}
+				keep_end = grid.last() == Some(&step_end) || step_end == arc_end;
+			}
+
+			if let Some((event_et, event_state, _)) = &interruption {
+				ets.push(*event_et);
+				states.push(event_state.clone());
+				break;
+			}
+			if keep_end {
+				ets.push(next_et);
+				states.push(state.clone());
+			}
+			last = (next_et, state);
+			values = next_values;
+		}
+
+		et = ets[ets.len() - 1];
+		y = states[states.len() - 1].clone();
+		segments.push(Segment { ets, states });
+
+		// Stop the propagation at the event, or maneuver and resume the arc
+		if let Some((_, _, i)) = interruption {
+			let (detector, idx) = &detectors[i];
+			match (detector.event.action, idx) {
+				(events::Action::Maneuver(frame, dv), Some(idx)) => {
+					let maneuver = maneuvers::Maneuver {
+						et,
+						body: detector.event.body,
+						frame,
+						dv,
+					};
+					let rel_state = detector.relative_state(et, &y)?;
+					let dv = if backward {
+						maneuver.undo_dv_j2000(&rel_state)?
+					} else {
+						maneuver.dv_j2000(&rel_state)
+					};
+					y.slice_mut(s![(idx * STATE_SIZE + 3)..(idx * STATE_SIZE + 6)])
+						.scaled_add(if backward { -1f64 } else { 1f64 }, &dv);
+				}
+				_ => break,
+			}
+		}
+
+		if et == etfinal {
+			break;
+		} else if et == arc_end {
+			arc_end = breaks.next().unwrap_or(etfinal);
+		}
+	}
+
+	Ok((segments, occurrences))
+}
+
+/// Epochs origin + k * step of an output grid that follow x0, up to x1. step is negative going
+/// backward. Grid epochs that only miss x0 or x1 due to rounding are taken to be x0 or x1
+fn grid_epochs(origin: f64, step: f64, x0: f64, x1: f64) -> Vec<f64> {
+	let mut k = ((x0 - origin) / step + 1e-9).floor() + 1.0;
+	let mut epochs = Vec::new();
+	loop {
+		let x = origin + k * step;
+		let remaining = (x1 - x) / step;
+		if remaining <= 1e-9 {
+			if remaining >= -1e-9 {
+				epochs.push(x1);
+			}
+			return epochs;
+		}
+		epochs.push(x);
+		k += 1.0;
+	}
+}
+
+/// Create solver object based on config on the heap (since exact type is unknown), propagating
+/// backward in time if etfinal precedes et0. If an output grid (origin, step) is given, the solver
+/// yields states at the epochs origin +/- k * step instead of at its own steps
+pub fn build_solver<'a, F>(
+	config: SolverConfig,
+	f: F,
+	et0: f64,
+	y0: &Array1<f64>,
+	etfinal: f64,
+	mus: &[f64],
+	output_grid: Option<(f64, f64)>,
+) -> Box<dyn solvers::Solver + 'a>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String> + Clone + 'a,
+{
+	if etfinal > et0 {
+		return build_forward_solver(config, f, et0, y0, etfinal, mus, output_grid);
+	}
+
+	let reverse = |y: &Array1<f64>| solvers::reverse_velocities(y, STATE_SIZE);
+	let reversed_f = move |et: f64, y: &Array1<f64>| Ok(-reverse(&f(-et, &reverse(y))?));
+	Box::new(solvers::Backward::new(
+		build_forward_solver(
+			config,
+			reversed_f,
+			-et0,
+			&reverse(y0),
+			-etfinal,
+			mus,
+			output_grid.map(|(origin, step)| (-origin, step)),
+		),
+		STATE_SIZE,
+	))
+}
+
+fn build_forward_solver<'a, F>(
+	config: SolverConfig,
+	f: F,
+	et0: f64,
+	y0: &Array1<f64>,
+	etfinal: f64,
+	mus: &[f64],
+	output_grid: Option<(f64, f64)>,
+) -> Box<dyn solvers::Solver + 'a>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String> + Clone + 'a,
+{
+	let dense_f = f.clone();
+	let solver: Box<dyn solvers::Solver + 'a> = match config {
+		SolverConfig::Rk4 { h } => Box::new(solvers::Rk4::new(f, h, et0, y0, etfinal)),
+		SolverConfig::Euler { h } => Box::new(solvers::Euler::new(f, h, et0, y0, etfinal)),
+		SolverConfig::Dopri45 {
+			h,
+			atol,
+			rtol,
+			control,
+		} => Box::new(solvers::Dopri45::new(
+			f, h, et0, y0, etfinal, atol, rtol, control,
+		)),
+		SolverConfig::Dop853 {
+			h,
+			atol,
+			rtol,
+			control,
+		} => Box::new(solvers::Dop853::new(
+			f, h, et0, y0, etfinal, atol, rtol, control,
+		)),
+		SolverConfig::Rkf78 {
+			h,
+			atol,
+			rtol,
+			control,
+		} => Box::new(solvers::Rkf78::new(
+			f, h, et0, y0, etfinal, atol, rtol, control,
+		)),
+		SolverConfig::Abm {
+			h,
+			atol,
+			rtol,
+			control,
+		} => Box::new(solvers::Abm::new(
+			f, h, et0, y0, etfinal, atol, rtol, control,
+		)),
+		SolverConfig::GaussJackson { h } => Box::new(solvers::GaussJackson::new(
+			f, h, et0, y0, etfinal, STATE_SIZE,
+		)),
+		SolverConfig::Symplectic { h, composition } => Box::new(solvers::Symplectic::new(
+			f,
+			h,
+			et0,
+			y0,
+			etfinal,
+			composition,
+			STATE_SIZE,
+		)),
+		SolverConfig::WisdomHolman { h } => Box::new(solvers::WisdomHolman::new(
+			f, h, et0, y0, etfinal, mus, STATE_SIZE,
+		)),
+		SolverConfig::Ias15 { h, control } => Box::new(solvers::Ias15::new(
+			f, h, et0, y0, etfinal, control, STATE_SIZE,
+		)),
+		SolverConfig::BulirschStoer {
+			h,
+			atol,
+			rtol,
+			control,
+		} => Box::new(solvers::BulirschStoer::new(
+			f, h, et0, y0, etfinal, atol, rtol, control,
+		)),
+	};
+
+	match output_grid {
+		Some((origin, step)) => Box::new(solvers::Dense::new(
+			solver, dense_f, origin, step, et0, y0, etfinal, STATE_SIZE,
+		)),
+		None => solver,
+	}
 }
@@ -0,0 +1,64 @@
+use ndarray::Array1;
+
+/// Speed of light in m/s
+const C: f64 = 299792458.0;
+
+/// Post-Newtonian correction to apply (PPN parameters beta = gamma = 1)
+pub enum RelativityModel {
+	/// Schwarzschild term of a single central body acting on all other propagated bodies
+	Schwarzschild { body: i32, mu: f64 },
+	/// Einstein-Infeld-Hoffmann equations for all massive bodies, including attractors
+	Eih,
+}
+
+/// Schwarzschild correction for a body at position r with velocity v relative to a central body
+pub fn schwarzschild(mu: f64, r: &Array1<f64>, v: &Array1<f64>) -> Array1<f64> {
+	let r_norm = r.dot(r).sqrt();
+	let fac = mu / (C * C * r_norm.powi(3));
+	fac * ((4.0 * mu / r_norm - v.dot(v)) * r + 4.0 * r.dot(v) * v)
+}
+
+/// Corrections to the Newtonian accelerations of the first n bodies, according to the
+/// Einstein-Infeld-Hoffmann equations (as used for the JPL DE ephemerides)
+pub fn eih(pos: &[Array1<f64>], vel: &[Array1<f64>], mus: &[f64], n: usize) -> Vec<Array1<f64>> {
+	let c2 = C * C;
+	let m = pos.len();
+
+	// Newtonian accelerations and gravitational potentials of all bodies
+	let mut acc = vec![Array1::<f64>::zeros(3); m];
+	let mut pot = vec![0f64; m];
+	for i in 0..m {
+		for k in (0..m).filter(|&k| k != i && mus[k] != 0f64) {
+			let r = &pos[k] - &pos[i];
+			let r_norm = r.dot(&r).sqrt();
+			pot[i] += mus[k] / r_norm;
+			acc[i] += &(mus[k] / r_norm.powi(3) * &r);
+		}
+	}
+
+	(0..n)
+		.map(|i| {
+			let mut da = Array1::<f64>::zeros(3);
+			let (vi, vi2) = (&vel[i], vel[i].dot(&vel[i]));
+
+			for j in (0..m).filter(|&j| j != i && mus[j] != 0f64) {
+				let vj = &vel[j];
+				// Vector from body i to source j
+				let r = &pos[j] - &pos[i];
+				let r_norm = r.dot(&r).sqrt();
+				let mu_r3 = mus[j] / r_norm.powi(3);
+
+				let rv = r.dot(vj) / r_norm;
+				let fac = (-4.0 * pot[i] - pot[j] + vi2 + 2.0 * vj.dot(vj)
+					- 4.0 * vi.dot(vj)
+					- 1.5 * rv * rv + 0.5 * r.dot(&acc[j]))
+					/ c2;
+
+				da += &(mu_r3 * fac * &r);
+				da += &(mu_r3 / c2 * -r.dot(&(4.0 * vi - 3.0 * vj)) * (vi - vj));
+				da += &(3.5 / c2 * mus[j] / r_norm * &acc[j]);
+			}
+			da
+		})
+		.collect()
+}
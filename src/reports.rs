@@ -0,0 +1,279 @@
+use crate::covariance::{MappedCovariance, KM2_TO_M2};
+use crate::eclipses;
+use crate::propagate::Segment;
+use crate::spice_utils;
+use crate::variational;
+use ndarray::{s, Array1};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Path of a report written alongside the output SPK, e. g. 'example_close_approaches.csv' for
+/// 'example.bsp', name 'close_approaches' and extension 'csv'
+pub fn path_alongside(output_file: &str, name: &str, extension: &str) -> String {
+	let path = Path::new(output_file);
+	let stem = path
+		.file_stem()
+		.and_then(|s| s.to_str())
+		.unwrap_or("output");
+	path.with_file_name(format!("{stem}_{name}.{extension}"))
+		.to_string_lossy()
+		.into_owned()
+}
+
+/// Local minimum of the distance between a small body and another body
+pub struct CloseApproach {
+	pub et: f64,
+	pub body: i32,
+	pub target: i32,
+	/// Minimum distance in m
+	pub distance: f64,
+	/// Relative velocity at the minimum distance in m/s
+	pub velocity: f64,
+	/// Whether the minimum distance is less than the target's equatorial radius, if it is known
+	pub impact: Option<bool>,
+}
+
+impl CloseApproach {
+	/// Close approach with the body's state relative to the target at its epoch
+	pub fn new(et: f64, body: i32, target: i32, rel_state: &Array1<f64>) -> Result<Self, String> {
+		let r = rel_state.slice(s![..3]);
+		let v = rel_state.slice(s![3..6]);
+		let distance = r.dot(&r).sqrt();
+
+		let impact = if spice_utils::has_body_constant(target, "RADII") {
+			Some(distance < spice_utils::body_constants(target, "RADII", 3)?[0] * 1000f64)
+		} else {
+			None
+		};
+
+		Ok(Self {
+			et,
+			body,
+			target,
+			distance,
+			velocity: v.dot(&v).sqrt(),
+			impact,
+		})
+	}
+}
+
+/// Write close approaches to a CSV file, with distances in km and velocities in km/s. Impacts are
+/// left empty where the radius of the target is unknown
+pub fn write_close_approaches(path: &str, approaches: &[CloseApproach]) -> Result<(), String> {
+	let mut csv = String::from("UTC,BODY,TARGET,DISTANCE_KM,VELOCITY_KM_S,IMPACT\n");
+	for approach in approaches {
+		let impact = match approach.impact {
+			Some(true) => "yes",
+			Some(false) => "no",
+			None => "",
+		};
+		csv.push_str(&format!(
+			"{},{},{},{:.6},{:.9},{}\n",
+			spice_utils::et2utc(approach.et),
+			approach.body,
+			approach.target,
+			approach.distance / 1000f64,
+			approach.velocity / 1000f64,
+			impact
+		));
+	}
+
+	std::fs::write(path, csv)
+		.map_err(|e| format!("Could not write close approach report '{path}': {e}"))
+}
+
+/// Write shadow intervals to a CSV file, with their durations in s
+pub fn write_eclipses(path: &str, intervals: &[eclipses::Interval]) -> Result<(), String> {
+	let mut csv = String::from("BODY,OCCULTER,SHADOW,START_UTC,STOP_UTC,DURATION_S\n");
+	for interval in intervals {
+		csv.push_str(&format!(
+			"{},{},{},{},{},{:.3}\n",
+			interval.body,
+			interval.occulter,
+			interval.shadow,
+			spice_utils::et2utc(interval.start),
+			spice_utils::et2utc(interval.stop),
+			interval.stop - interval.start
+		));
+	}
+
+	std::fs::write(path, csv).map_err(|e| format!("Could not write eclipse report '{path}': {e}"))
+}
+
+/// Write the state transition matrices of stm_bodies at each state of the segments to a CSV file,
+/// row by row. Each segment after the first starts at the epoch the previous one ended at, so its
+/// first state is skipped
+pub fn write_stm(
+	path: &str,
+	segments: &[Segment],
+	bodies: usize,
+	stm_bodies: &[i32],
+) -> Result<(), String> {
+	let mut csv = String::from("UTC,BODY");
+	for row in 1..=6 {
+		for column in 1..=6 {
+			csv.push_str(&format!(",PHI_{row}_{column}"));
+		}
+	}
+	csv.push('\n');
+
+	for (i, segment) in segments.iter().enumerate() {
+		for (et, state) in segment
+			.ets
+			.iter()
+			.zip(&segment.states)
+			.skip(usize::from(i > 0))
+		{
+			let utc = spice_utils::et2utc(*et);
+			for (k, body) in stm_bodies.iter().enumerate() {
+				csv.push_str(&format!("{utc},{body}"));
+				for value in variational::stm(state, bodies, k).iter() {
+					csv.push_str(&format!(",{value:e}"));
+				}
+				csv.push('\n');
+			}
+		}
+	}
+
+	std::fs::write(path, csv)
+		.map_err(|e| format!("Could not write state transition matrices '{path}': {e}"))
+}
+
+/// Lower triangle of a covariance row by row, in km^2, km^2/s and km^2/s^2
+fn lower_triangle(covariance: &ndarray::Array2<f64>) -> Vec<f64> {
+	let mut values = Vec::with_capacity(21);
+	for i in 0..6 {
+		for j in 0..=i {
+			values.push(covariance[[i, j]] / KM2_TO_M2);
+		}
+	}
+	values
+}
+
+/// Write mapped covariances of small bodies, segment by segment, to a CSV file, one row per epoch
+/// and frame (J2000 or RTN) with the lower triangle of the covariance in km^2, km^2/s and
+/// km^2/s^2. As in write_stm, the first state of each segment after the first is skipped
+pub fn write_covariance_csv(
+	path: &str,
+	covariances: &[Vec<Vec<MappedCovariance>>],
+) -> Result<(), String> {
+	let mut csv = String::from("UTC,BODY,FRAME");
+	for i in 1..=6 {
+		for j in 1..=i {
+			csv.push_str(&format!(",C_{i}_{j}"));
+		}
+	}
+	csv.push('\n');
+
+	let rows = covariances.iter().flat_map(|segments| {
+		segments
+			.iter()
+			.enumerate()
+			.flat_map(|(i, segment)| segment.iter().skip(usize::from(i > 0)))
+	});
+	for covariance in rows {
+		let utc = spice_utils::et2utc(covariance.et);
+		for (frame, matrix) in [("J2000", &covariance.j2000), ("RTN", &covariance.rtn)] {
+			csv.push_str(&format!("{utc},{},{frame}", covariance.body));
+			for value in lower_triangle(matrix) {
+				csv.push_str(&format!(",{value:e}"));
+			}
+			csv.push('\n');
+		}
+	}
+
+	std::fs::write(path, csv).map_err(|e| format!("Could not write covariances '{path}': {e}"))
+}
+
+/// Current UTC time as an ISO calendar timestamp
+fn now_utc() -> String {
+	let secs = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_secs_f64())
+		.unwrap_or(0f64);
+	// Unix time counts 86400 s per UTC day since the Julian date 2440587.5
+	let jd = 2440587.5 + secs / 86400f64;
+	spice_utils::et2utc(spice::str2et(&format!("JD {jd:.8}")))
+}
+
+/// Write the states and mapped covariances of small bodies to a CCSDS OEM file, with one segment
+/// per body and propagated segment, so that the velocity changes at maneuvers are not interpolated
+/// across. States are relative to the observing body in km and km/s, and each epoch has a
+/// covariance in EME2000 (J2000) and in RTN
+pub fn write_covariance_oem(
+	path: &str,
+	covariances: &[Vec<Vec<MappedCovariance>>],
+	cb_id: i32,
+) -> Result<(), String> {
+	let mut oem = format!(
+		"CCSDS_OEM_VERS = 2.0\nCREATION_DATE = {}\nORIGINATOR = {}\n",
+		now_utc(),
+		env!("CARGO_PKG_NAME")
+	);
+
+	for body_segments in covariances {
+		// OEM epochs must be ascending, also for backward propagations
+		let mut body_segments = body_segments
+			.iter()
+			.filter(|segment| !segment.is_empty())
+			.map(|segment| {
+				let mut segment = segment.iter().collect::<Vec<_>>();
+				segment.sort_by(|a, b| a.et.partial_cmp(&b.et).unwrap());
+				segment
+			})
+			.collect::<Vec<_>>();
+		body_segments.sort_by(|a, b| a[0].et.partial_cmp(&b[0].et).unwrap());
+
+		for segment in &body_segments {
+			let body = segment[0].body;
+			let start = spice_utils::et2utc(segment[0].et);
+			let stop = spice_utils::et2utc(segment[segment.len() - 1].et);
+			oem.push_str("\nMETA_START\n");
+			oem.push_str(&format!("OBJECT_NAME = {}\n", spice_utils::body_name(body)));
+			oem.push_str(&format!("OBJECT_ID = {body}\n"));
+			oem.push_str(&format!(
+				"CENTER_NAME = {}\n",
+				spice_utils::body_name(cb_id)
+			));
+			oem.push_str("REF_FRAME = EME2000\nTIME_SYSTEM = UTC\n");
+			oem.push_str(&format!("START_TIME = {start}\nSTOP_TIME = {stop}\n"));
+			oem.push_str("META_STOP\n\n");
+
+			for covariance in segment {
+				let s = &covariance.state / 1000f64;
+				oem.push_str(&format!(
+					"{} {:.6} {:.6} {:.6} {:.9} {:.9} {:.9}\n",
+					spice_utils::et2utc(covariance.et),
+					s[0],
+					s[1],
+					s[2],
+					s[3],
+					s[4],
+					s[5]
+				));
+			}
+
+			oem.push_str("\nCOVARIANCE_START\n");
+			for covariance in segment {
+				let utc = spice_utils::et2utc(covariance.et);
+				for (frame, matrix) in [("EME2000", &covariance.j2000), ("RTN", &covariance.rtn)] {
+					oem.push_str(&format!("EPOCH = {utc}\nCOV_REF_FRAME = {frame}\n"));
+					let values = lower_triangle(matrix);
+					let mut values = values.iter();
+					for i in 0..6 {
+						let row = values
+							.by_ref()
+							.take(i + 1)
+							.map(|v| format!("{v:.7e}"))
+							.collect::<Vec<_>>();
+						oem.push_str(&row.join(" "));
+						oem.push('\n');
+					}
+				}
+			}
+			oem.push_str("COVARIANCE_STOP\n");
+		}
+	}
+
+	std::fs::write(path, oem).map_err(|e| format!("Could not write OEM file '{path}': {e}"))
+}
@@ -1,6 +1,17 @@
 use crate::cli;
+use crate::constants;
+use crate::covariance;
+use crate::drag;
+use crate::eclipses;
+use crate::events;
+use crate::maneuvers;
+use crate::ode;
 use crate::propagate;
+use crate::reports;
+use crate::solvers;
 use crate::spice_utils;
+use crate::srp;
+use crate::thrust;
 use std::time::SystemTime;
 
 pub fn run(
@@ -10,13 +21,40 @@ pub fn run(
 		bodies,
 		small_bodies,
 		attractors,
+		zonal_bodies,
+		zonal_degree,
+		gravity_field,
+		gravity_field_body,
+		gravity_degree,
+		srp,
+		shadow_model,
+		drag,
+		atmosphere_body,
+		atmosphere,
+		drag_max_altitude,
+		comet_forces,
+		yarkovsky,
+		relativity,
+		maneuvers,
+		thrust_arcs,
+		events,
+		close_approaches,
+		eclipses,
+		stm,
+		covariance,
+		process_noise,
+		covariance_format,
 		t0,
 		atol,
+		min_step,
+		max_step,
+		max_rejections,
 		tfinal,
 		h,
 		method,
 		output_file,
 		fts,
+		output_step,
 	}: cli::Args,
 ) -> Result<(), String> {
 	if bodies.is_none() && small_bodies.is_none() {
@@ -25,7 +63,33 @@ pub fn run(
 		return Err("'bodies' cannot affect trajectories of 'attractors' - Providing both would result in inconsistencies".to_string());
 	} else if attractors.is_some() && cb_id.is_none() {
 		return Err("--cb-id is requried when using --attractors".to_string());
+	} else if attractors.is_some()
+		&& matches!(
+			method.as_deref(),
+			Some("leapfrog" | "yoshida4" | "yoshida6" | "wisdom-holman")
+		) {
+		return Err("Symplectic methods cannot be used with --attractors - Their time-dependent states can't be split into drift and kick".to_string());
+	} else if method.as_deref() == Some("wisdom-holman") && bodies.is_none() {
+		return Err(
+			"--method wisdom-holman requires --bodies, the first of which is the central body"
+				.to_string(),
+		);
+	} else if matches!(zonal_degree, Some(n) if n < 2) {
+		return Err("--zonal-degree must be at least 2".to_string());
+	} else if matches!(output_step, Some(step) if step <= 0f64) {
+		return Err("--output-step must be positive".to_string());
+	} else if matches!(close_approaches, Some(km) if km <= 0f64) {
+		return Err("--close-approaches must be positive".to_string());
+	} else if stm && small_bodies.is_none() {
+		return Err("--stm requires --small-bodies".to_string());
+	} else if matches!(process_noise, Some(q) if q < 0f64) {
+		return Err("--process-noise must not be negative".to_string());
 	}
+	let oem = match covariance_format.as_deref() {
+		Some("csv") | None => false,
+		Some("oem") => true,
+		Some(format) => return Err(format!("Unknown covariance format: {format}")),
+	};
 	// Load included kernels
 	spice::furnsh("spice/included.tm");
 	// Load user-provided kernels
@@ -35,25 +99,221 @@ pub fn run(
 	let bodies = spice_utils::naif_ids(&bodies.unwrap_or_default())?;
 	let small_bodies = spice_utils::naif_ids(&small_bodies.unwrap_or_default())?;
 	let attractors = spice_utils::naif_ids(&attractors.unwrap_or_default())?;
+	let zonal_bodies = spice_utils::naif_ids(&zonal_bodies.unwrap_or_default())?;
+	let gravity_field_body = match gravity_field_body {
+		Some(body) => Some(spice_utils::naif_ids(&[body])?[0]),
+		None => None,
+	};
+
+	if let Some(id) = gravity_field_body.filter(|id| zonal_bodies.contains(id)) {
+		return Err(format!(
+			"Zonal harmonics of body {id} are already part of its gravity field - Providing both would count them twice"
+		));
+	}
+
+	let mut srp_bodies = Vec::new();
+	for spec in srp.unwrap_or_default() {
+		let (body, params) = cli::parse_body_spec(&spec, "AREA:MASS:CR")?;
+		srp_bodies.push((spice_utils::naif_ids(&[body])?[0], params));
+	}
+
+	let shadow = match shadow_model.as_deref() {
+		Some("cylindrical") => Some(srp::ShadowModel::Cylindrical),
+		Some("conical") => Some(srp::ShadowModel::Conical),
+		Some(model) => return Err(format!("Unknown shadow model: {model}")),
+		None => None,
+	};
+
+	let drag = match (drag, atmosphere_body, atmosphere) {
+		(Some(specs), Some(atmosphere_body), Some(atmosphere)) => {
+			let mut drag_bodies = Vec::new();
+			for spec in specs {
+				let (body, params) = cli::parse_body_spec(&spec, "AREA:MASS:CD")?;
+				drag_bodies.push((spice_utils::naif_ids(&[body])?[0], params));
+			}
+			Some(propagate::DragConfig {
+				bodies: drag_bodies,
+				atmosphere_body: spice_utils::naif_ids(&[atmosphere_body])?[0],
+				density: drag::density_model_from_spec(&atmosphere)?,
+				max_altitude: drag_max_altitude.unwrap_or(1000f64) * 1000f64,
+			})
+		}
+		(None, None, None) => None,
+		_ => return Err("--drag requires --atmosphere-body and --atmosphere".to_string()),
+	};
+
+	let mut comet_bodies = Vec::new();
+	for spec in comet_forces.unwrap_or_default() {
+		let (body, params) = cli::parse_body_spec(&spec, "A1:A2:A3")?;
+		comet_bodies.push((spice_utils::naif_ids(&[body])?[0], params));
+	}
+
+	let mut yarkovsky_bodies = Vec::new();
+	for spec in yarkovsky.unwrap_or_default() {
+		let (body, params) = cli::parse_body_spec::<1>(&spec, "A2")?;
+		yarkovsky_bodies.push((spice_utils::naif_ids(&[body])?[0], params.map(|[a2]| a2)));
+	}
+
+	let relativity = match relativity.as_deref() {
+		Some("schwarzschild") => Some(propagate::RelativityConfig::Schwarzschild),
+		Some("eih") => Some(propagate::RelativityConfig::Eih),
+		Some(model) => return Err(format!("Unknown relativity model: {model}")),
+		None => None,
+	};
+
+	let maneuvers = match maneuvers {
+		Some(path) => maneuvers::Maneuver::from_file(&path)?,
+		None => Vec::new(),
+	};
+
+	let mut events = match events {
+		Some(path) => events::Event::from_file(&path)?,
+		None => Vec::new(),
+	};
+
+	// Close approaches are periapses of each small body about bodies and attractors
+	let user_events = events.len();
+	if close_approaches.is_some() {
+		for &body in &small_bodies {
+			for &target in bodies.iter().chain(&attractors) {
+				events.push(events::Event {
+					body,
+					condition: events::Condition::Periapsis,
+					target,
+					action: events::Action::Record,
+				});
+			}
+		}
+	}
+
+	// Eclipses are crossings of the shadows cast by any body or attractor whose radius is known
+	let eclipse_events = events.len();
+	if eclipses {
+		for &body in &small_bodies {
+			for &occulter in bodies.iter().chain(&attractors) {
+				if occulter == body
+					|| occulter == constants::SUN
+					|| !spice_utils::has_body_constant(occulter, "RADII")
+				{
+					continue;
+				}
+				for shadow in [eclipses::Shadow::Penumbra, eclipses::Shadow::Umbra] {
+					events.push(events::Event {
+						body,
+						condition: events::Condition::Shadow(shadow),
+						target: occulter,
+						action: events::Action::Record,
+					});
+				}
+			}
+		}
+	}
+
+	let covariances = match covariance {
+		Some(path) => covariance::InitialCovariance::from_file(&path)?,
+		None => Vec::new(),
+	};
+
+	// Covariances are mapped with the state transition matrices of their bodies
+	let mut stm_bodies = if stm {
+		small_bodies.clone()
+	} else {
+		Vec::new()
+	};
+	for initial in &covariances {
+		if !stm_bodies.contains(&initial.body) {
+			stm_bodies.push(initial.body);
+		}
+	}
+
+	let thrust = match thrust_arcs {
+		Some(path) => thrust::ThrustArc::from_file(&path)?,
+		None => Vec::new(),
+	};
 
 	let cb_id = cb_id.unwrap_or_else(|| bodies[0]);
 
+	let default_control = solvers::StepControl::default();
+	let control = solvers::StepControl {
+		min_h: min_step.unwrap_or(default_control.min_h),
+		max_h: max_step.unwrap_or(default_control.max_h),
+		max_rejections: max_rejections.unwrap_or(default_control.max_rejections),
+	};
+	if !(control.min_h > 0f64 && control.min_h <= control.max_h) {
+		return Err("--min-step must be positive and no greater than --max-step".to_string());
+	}
+
 	// Create solver config based on CLI args
 	let solver = match method.as_deref() {
 		Some("rk4") | None => propagate::SolverConfig::Rk4 { h },
 		Some("euler") => propagate::SolverConfig::Euler { h },
+		Some("gauss-jackson") => propagate::SolverConfig::GaussJackson { h },
+		Some("leapfrog") => propagate::SolverConfig::Symplectic {
+			h,
+			composition: solvers::Composition::Leapfrog,
+		},
+		Some("yoshida4") => propagate::SolverConfig::Symplectic {
+			h,
+			composition: solvers::Composition::Yoshida4,
+		},
+		Some("yoshida6") => propagate::SolverConfig::Symplectic {
+			h,
+			composition: solvers::Composition::Yoshida6,
+		},
+		Some("wisdom-holman") => propagate::SolverConfig::WisdomHolman { h },
+		Some("ias15") => propagate::SolverConfig::Ias15 { h, control },
 		Some("dopri45") => propagate::SolverConfig::Dopri45 {
 			h,
 			atol: atol.unwrap_or(50000f64),
 			rtol: 0f64,
+			control,
+		},
+		Some("dop853") => propagate::SolverConfig::Dop853 {
+			h,
+			atol: atol.unwrap_or(50000f64),
+			rtol: 0f64,
+			control,
+		},
+		Some("rkf78") => propagate::SolverConfig::Rkf78 {
+			h,
+			atol: atol.unwrap_or(50000f64),
+			rtol: 0f64,
+			control,
+		},
+		Some("abm") => propagate::SolverConfig::Abm {
+			h,
+			atol: atol.unwrap_or(50000f64),
+			rtol: 0f64,
+			control,
+		},
+		Some("bulirsch-stoer") => propagate::SolverConfig::BulirschStoer {
+			h,
+			atol: atol.unwrap_or(50000f64),
+			rtol: 0f64,
+			control,
 		},
 		Some(method) => return Err(format!("Unknown method: {method}")),
 	};
 
+	let forces = propagate::ForceModelConfig {
+		zonal_bodies,
+		zonal_degree: zonal_degree.unwrap_or(2),
+		gravity_field: gravity_field_body
+			.zip(gravity_field)
+			.map(|(id, path)| (id, path, gravity_degree)),
+		srp: srp_bodies,
+		shadow,
+		drag,
+		comet_forces: comet_bodies,
+		yarkovsky: yarkovsky_bodies,
+		relativity,
+		thrust,
+	};
+
 	let start = SystemTime::now();
 
 	// Propagate trajectories
-	let (ets, states) = propagate::propagate(
+	let (segments, occurrences) = propagate::propagate(
 		&bodies,
 		&small_bodies,
 		&attractors,
@@ -61,22 +321,118 @@ pub fn run(
 		&t0,
 		&tfinal,
 		solver,
+		output_step,
+		forces,
+		&maneuvers,
+		&events,
+		&stm_bodies,
 	)?;
 
-	// Write propagated trajectories to new SPK kernel
-	println!("Writing to SPK...");
-	spice_utils::write_to_spk(
-		&output_file,
-		&bodies
+	let (occurrences, reported): (Vec<_>, Vec<_>) =
+		occurrences.into_iter().partition(|o| o.event < user_events);
+
+	if !occurrences.is_empty() {
+		println!("Events:");
+	}
+	for occurrence in &occurrences {
+		let event = &events[occurrence.event];
+		println!(
+			"{} {} {} {}",
+			spice_utils::et2utc(occurrence.et),
+			event.body,
+			event.condition,
+			event.target
+		);
+	}
+
+	if let Some(km) = close_approaches {
+		let mut approaches = Vec::new();
+		let periapses = reported
 			.iter()
-			.cloned()
-			.chain(small_bodies.iter().cloned())
-			.collect::<Vec<_>>(),
-		&ets,
-		&states,
-		cb_id,
-		fts.unwrap_or(1f32),
-	)?;
+			.filter(|o| events[o.event].condition == events::Condition::Periapsis);
+		for periapsis in periapses {
+			let event = &events[periapsis.event];
+			let approach = reports::CloseApproach::new(
+				periapsis.et,
+				event.body,
+				event.target,
+				&periapsis.relative_state,
+			)?;
+			if approach.distance < km * 1000f64 {
+				approaches.push(approach);
+			}
+		}
+		approaches.sort_by(|a, b| a.et.partial_cmp(&b.et).unwrap());
+
+		let path = reports::path_alongside(&output_file, "close_approaches", "csv");
+		println!("Writing {} close approaches to {path}...", approaches.len());
+		reports::write_close_approaches(&path, &approaches)?;
+	}
+
+	let ids = bodies
+		.iter()
+		.cloned()
+		.chain(small_bodies.iter().cloned())
+		.collect::<Vec<_>>();
+
+	if eclipses {
+		let intervals =
+			eclipses::intervals(&segments, &events, eclipse_events, &reported, &ids, cb_id)?;
+
+		let path = reports::path_alongside(&output_file, "eclipses", "csv");
+		println!("Writing {} shadow intervals to {path}...", intervals.len());
+		reports::write_eclipses(&path, &intervals)?;
+	}
+
+	if stm {
+		let path = reports::path_alongside(&output_file, "stm", "csv");
+		println!("Writing state transition matrices to {path}...");
+		reports::write_stm(&path, &segments, ids.len(), &small_bodies)?;
+	}
+
+	if !covariances.is_empty() {
+		let cb_idx = bodies.iter().position(|&id| id == cb_id);
+		let mut mapped = Vec::with_capacity(covariances.len());
+		for initial in &covariances {
+			let k = stm_bodies
+				.iter()
+				.position(|&id| id == initial.body)
+				.unwrap();
+			let idx = ids.iter().position(|&id| id == initial.body).unwrap();
+			mapped.push(covariance::map(
+				&segments,
+				ids.len(),
+				idx,
+				cb_idx,
+				k,
+				initial,
+				process_noise,
+			)?);
+		}
+
+		let extension = if oem { "oem" } else { "csv" };
+		let path = reports::path_alongside(&output_file, "covariance", extension);
+		println!("Writing covariances to {path}...");
+		if oem {
+			reports::write_covariance_oem(&path, &mapped, cb_id)?;
+		} else {
+			reports::write_covariance_csv(&path, &mapped)?;
+		}
+	}
+
+	// Write propagated trajectories to new SPK kernel, one segment per arc between maneuvers
+	println!("Writing to SPK...");
+	for segment in segments {
+		spice_utils::write_to_spk(
+			&output_file,
+			&ids,
+			&segment.states,
+			&segment.ets,
+			cb_id,
+			fts.unwrap_or(1f32),
+			ode::STATE_SIZE,
+		)?;
+	}
 
 	// Cleanup - unload kernels
 	spice::unload("spice/included.tm");
@@ -1,5 +1,24 @@
 use ndarray::Array1;
 
+mod abm;
+mod bulirsch_stoer;
+mod dense;
+mod dop853;
+mod gauss_jackson;
+mod ias15;
+mod rkf78;
+mod symplectic;
+mod wisdom_holman;
+pub use abm::Abm;
+pub use bulirsch_stoer::BulirschStoer;
+pub use dense::{hermite, Dense};
+pub use dop853::Dop853;
+pub use gauss_jackson::GaussJackson;
+pub use ias15::Ias15;
+pub use rkf78::Rkf78;
+pub use symplectic::{Composition, Symplectic};
+pub use wisdom_holman::WisdomHolman;
+
 #[rustfmt::skip]
 pub mod step_fns {
 	use ndarray::Array1;
@@ -28,7 +47,8 @@ pub mod step_fns {
 		let next_y = y + dy * h;
 		Ok((x + h, next_y))
 	}
-	#[allow(clippy::too_many_arguments)]
+	/// Also returns the stages, from which the continuous extension of the step is formed
+	#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 	pub fn dopri(
 		f: impl Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
 		x: f64,
@@ -38,13 +58,13 @@ pub mod step_fns {
 		rtol: f64,
 		safety_fac: f64,
 		max_relative_dh: f64,
-	) -> Result<(f64, Array1<f64>, f64), String> {
+	) -> Result<(f64, Array1<f64>, f64, f64, [Array1<f64>; 7]), String> {
 		let k1 = f(x, y)?;
-		let k2 = f(x + h*1.0/5.0,  &(y + h *  &k1*0.5))?;
+		let k2 = f(x + h*1.0/5.0,  &(y + h *  &k1*1.0/5.0))?;
 		let k3 = f(x + h*3.0/10.0, &(y + h * (&k1*3.0/40.0       + &k2*9.0/40.0)))?;
 		let k4 = f(x + h*4.0/5.0,  &(y + h * (&k1*44.0/45.0      + &k2*-56.0/15.0      + &k3*32.0/9.0)))?;
-		let k5 = f(x + h*8.0/9.0,  &(y + h * (&k1*19372.0/6561.0 + &k2*-25360.0/2187.0 + &k3*64448.0/6561.0 + &k4*-212.0/792.0)))?;
-		let k6 = f(x + h*1.0,      &(y + h * (&k1*9017.0/3168.0  + &k2*355.0/33.0      + &k3*46732.0/5247.0 + &k4*49.0/176.0     + &k5*-5103.0/18656.0)))?;
+		let k5 = f(x + h*8.0/9.0,  &(y + h * (&k1*19372.0/6561.0 + &k2*-25360.0/2187.0 + &k3*64448.0/6561.0 + &k4*-212.0/729.0)))?;
+		let k6 = f(x + h*1.0,      &(y + h * (&k1*9017.0/3168.0  + &k2*-355.0/33.0     + &k3*46732.0/5247.0 + &k4*49.0/176.0     + &k5*-5103.0/18656.0)))?;
 		let k7 = f(x + h*1.0,      &(y + h * (&k1*35.0/384.0     + &k3*500.0/1113.0    + &k4*125.0/192.0    + &k5*-2187.0/6784.0 + &k6*11.0/84.0)))?;
 
 		let hiord_y = y + h * &(&k1*35.0/384.0     + &k3*500.0/1113.0   + &k4*125.0/192.0 + &k5*-2187.0/6784.0    + &k6 * 11.0/84.0);
@@ -60,12 +80,145 @@ pub mod step_fns {
 		let new_h_fac = ((1.0 / err_norm).powf(0.2) * safety_fac).max(1.0 / max_relative_dh).min(max_relative_dh);
 		let h_new = new_h_fac * h;
 
-		Ok((x + h, hiord_y, h_new))
+		Ok((x + h, hiord_y, h_new, err_norm, [k1, k2, k3, k4, k5, k6, k7]))
+	}
+}
+
+/// Bounds on the adaptive step size control of embedded methods
+#[derive(Clone, Copy, Debug)]
+pub struct StepControl {
+	/// Smallest permitted step size. Failing to meet the tolerance at this size is an error
+	pub min_h: f64,
+	/// Largest permitted step size
+	pub max_h: f64,
+	/// Number of consecutive rejected attempts after which a step is considered to have failed
+	pub max_rejections: usize,
+}
+
+impl Default for StepControl {
+	fn default() -> Self {
+		Self {
+			min_h: 1e-6,
+			max_h: f64::INFINITY,
+			max_rejections: 100,
+		}
+	}
+}
+
+/// Step size control shared by embedded methods - rejected steps are retried with smaller step
+/// sizes within the bounds of a StepControl
+struct Controller {
+	control: StepControl,
+	/// Exponent of the error estimate in the step size update, 1 / (q + 1) for an estimate of order q
+	exponent: f64,
+	safety_factor: f64,
+	/// Bounds on the factor the step size may change by after a single step
+	min_relative_dh: f64,
+	max_relative_dh: f64,
+}
+
+impl Controller {
+	/// Attempt steps from x until one meets the tolerance, starting with step size h. `step` takes
+	/// a step of given size and returns the new state and its error estimate relative to the
+	/// tolerance. Returns the new x and state, and the step size to attempt next
+	fn advance(
+		&self,
+		method: &str,
+		x: f64,
+		xmax: f64,
+		mut h: f64,
+		mut step: impl FnMut(f64) -> Result<(Array1<f64>, f64), String>,
+	) -> Result<(f64, Array1<f64>, f64), String> {
+		let mut rejections = 0;
+		loop {
+			// Don't step past the end - the shortened final step may fall below the minimum
+			let last_step = h >= xmax - x;
+			if last_step {
+				h = xmax - x;
+			}
+
+			let (y, err_norm) = step(h)?;
+			let fac = (self.safety_factor * err_norm.powf(-self.exponent))
+				.clamp(self.min_relative_dh, self.max_relative_dh);
+
+			if err_norm <= 1.0 {
+				let x = if last_step { xmax } else { x + h };
+				return Ok((
+					x,
+					y,
+					(h * fac).clamp(self.control.min_h, self.control.max_h),
+				));
+			}
+
+			// Reject step and retry with a smaller one
+			rejections += 1;
+			if last_step && h < self.control.min_h {
+				return Err(format!(
+					"{method}: Error estimate {err_norm:.3e} exceeds tolerance on final step of size {h}, below the minimum step size {} (t = {x})",
+					self.control.min_h
+				));
+			} else if h <= self.control.min_h {
+				return Err(format!(
+					"{method}: Error estimate {err_norm:.3e} exceeds tolerance at minimum step size {} (t = {x})",
+					self.control.min_h
+				));
+			} else if rejections > self.control.max_rejections {
+				return Err(format!(
+					"{method}: Step at t = {x} rejected {rejections} times in a row"
+				));
+			}
+
+			h = (h * fac.min(1.0)).max(self.control.min_h);
+		}
 	}
 }
 
+/// 7-point Gauss-Legendre nodes and weights on [-1, 1], exact for polynomials up to degree 13
+const GAUSS_NODES: [f64; 7] = [
+	-0.9491079123427585,
+	-0.7415311855993945,
+	-0.4058451513773972,
+	0.0,
+	0.4058451513773972,
+	0.7415311855993945,
+	0.9491079123427585,
+];
+const GAUSS_WEIGHTS: [f64; 7] = [
+	0.1294849661688697,
+	0.2797053914892766,
+	0.3818300505051189,
+	0.4179591836734694,
+	0.3818300505051189,
+	0.2797053914892766,
+	0.1294849661688697,
+];
+
+/// Integral from a to b of the j-th Lagrange basis polynomial through the given nodes, multiplied
+/// by weight. Exact as long as the product is a polynomial of degree 13 or less
+fn integrate_basis(nodes: &[f64], j: usize, a: f64, b: f64, weight: impl Fn(f64) -> f64) -> f64 {
+	GAUSS_NODES
+		.iter()
+		.zip(&GAUSS_WEIGHTS)
+		.map(|(g, w)| {
+			let s = a + 0.5 * (g + 1.0) * (b - a);
+			let basis = nodes
+				.iter()
+				.enumerate()
+				.filter(|&(m, _)| m != j)
+				.map(|(_, sm)| (s - sm) / (nodes[j] - sm))
+				.product::<f64>();
+			0.5 * (b - a) * w * basis * weight(s)
+		})
+		.sum()
+}
+
 pub trait Solver {
 	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String>;
+
+	/// State at x within the last step taken, if the method comes with a continuous extension
+	fn interpolate(&self, _x: f64) -> Option<Array1<f64>> {
+		None
+	}
 }
 
 pub struct Rk4<F> {
@@ -144,6 +297,15 @@ where
 	}
 }
 
+/// Weights of the stages in the 4th order continuous extension of the Dormand-Prince 5(4) method,
+/// as given by Hairer, Norsett & Wanner. These need no further evaluations of f
+#[rustfmt::skip]
+const DOPRI_DENSE: [f64; 7] = [
+	-12715105075.0 / 11282082432.0, 0.0, 87487479700.0 / 32700410799.0,
+	-10690763975.0 / 1880347072.0, 701980252875.0 / 199316789632.0,
+	-1453857185.0 / 822651844.0, 69997945.0 / 29380423.0,
+];
+
 pub struct Dopri45<F> {
 	f: F,
 	h: f64,
@@ -152,22 +314,38 @@ pub struct Dopri45<F> {
 	xmax: f64,
 	atol: f64,
 	rtol: f64,
-	max_relative_dh: f64,
-	safety_factor: f64,
+	controller: Controller,
+	/// Start and size of the last step, and the coefficients of its continuous extension
+	dense: Option<(f64, f64, [Array1<f64>; 5])>,
 }
 impl<F> Dopri45<F> {
-	#[allow(dead_code)]
-	pub fn new(f: F, h: f64, x0: f64, y0: &Array1<f64>, xmax: f64, atol: f64, rtol: f64) -> Self {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		atol: f64,
+		rtol: f64,
+		control: StepControl,
+	) -> Self {
 		Self {
 			f,
-			h,
+			h: h.clamp(control.min_h, control.max_h),
 			x: x0,
 			y: y0.clone(),
 			xmax,
 			atol,
 			rtol,
-			max_relative_dh: 1.2,
-			safety_factor: 0.85,
+			controller: Controller {
+				control,
+				exponent: 0.2,
+				safety_factor: 0.85,
+				min_relative_dh: 0.2,
+				max_relative_dh: 1.2,
+			},
+			dense: None,
 		}
 	}
 }
@@ -179,23 +357,94 @@ where
 	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
 		if self.x >= self.xmax {
 			return Ok(None);
-		} else if self.x + self.h > self.xmax {
-			self.h = self.xmax - self.x;
 		}
 
-		let p = step_fns::dopri(
-			|x, y| (self.f)(x, y),
+		// Stages of the last attempted step, which is the accepted one once the controller returns
+		let mut stages = None;
+		let (x, y, h_new) = self
+			.controller
+			.advance("Dopri45", self.x, self.xmax, self.h, |h| {
+				let (_, y, _, err_norm, k) = step_fns::dopri(
+					|x, y| (self.f)(x, y),
+					self.x,
+					&self.y,
+					h,
+					self.atol,
+					self.rtol,
+					self.controller.safety_factor,
+					self.controller.max_relative_dh,
+				)?;
+				stages = Some((h, k));
+				Ok((y, err_norm))
+			})?;
+
+		let (h, k) = stages.unwrap();
+		let dy = &y - &self.y;
+		let spline = &k[0] * h - &dy;
+		let mut correction = Array1::zeros(y.len());
+		for (d, k) in DOPRI_DENSE.iter().zip(&k).filter(|(&d, _)| d != 0.0) {
+			correction.scaled_add(h * d, k);
+		}
+		let end_slope = &dy - &k[6] * h - &spline;
+		self.dense = Some((
 			self.x,
-			&self.y,
-			self.h,
-			self.atol,
-			self.rtol,
-			self.safety_factor,
-			self.max_relative_dh,
-		)?;
-		self.x = p.0;
-		self.y = p.1.clone();
-		self.h = p.2;
-		Ok(Some((p.0, p.1)))
+			h,
+			[self.y.clone(), dy, spline, end_slope, correction],
+		));
+
+		self.x = x;
+		self.y = y.clone();
+		self.h = h_new;
+		Ok(Some((x, y)))
+	}
+
+	fn interpolate(&self, x: f64) -> Option<Array1<f64>> {
+		let (x0, h, [y0, dy, spline, end_slope, correction]) = self.dense.as_ref()?;
+		let theta = (x - x0) / h;
+		let theta1 = 1.0 - theta;
+		let inner = spline + &((end_slope + &(correction * theta1)) * theta);
+		Some(y0 + &((dy + &(inner * theta1)) * theta))
+	}
+}
+
+/// Negate the velocities of a state made up of blocks of block_size elements, each starting with
+/// a position and a velocity in 3 dimensions
+pub fn reverse_velocities(y: &Array1<f64>, block_size: usize) -> Array1<f64> {
+	let mut reversed = y.clone();
+	for (i, value) in reversed.iter_mut().enumerate() {
+		if (3..6).contains(&(i % block_size)) {
+			*value = -*value;
+		}
+	}
+	reversed
+}
+
+/// Propagation backward in time. Wraps a solver integrating the time-reversed ODE forward, in
+/// terms of -t and with the velocities of its state negated (see reverse_velocities), and
+/// translates its states back. This keeps the relation between positions and velocities that
+/// symplectic and second order methods rely on
+pub struct Backward<'a> {
+	solver: Box<dyn Solver + 'a>,
+	block_size: usize,
+}
+
+impl<'a> Backward<'a> {
+	pub fn new(solver: Box<dyn Solver + 'a>, block_size: usize) -> Self {
+		Self { solver, block_size }
+	}
+}
+
+impl<'a> Solver for Backward<'a> {
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		Ok(self
+			.solver
+			.next_state()?
+			.map(|(x, y)| (-x, reverse_velocities(&y, self.block_size))))
+	}
+
+	fn interpolate(&self, x: f64) -> Option<Array1<f64>> {
+		self.solver
+			.interpolate(-x)
+			.map(|y| reverse_velocities(&y, self.block_size))
 	}
 }
@@ -0,0 +1,207 @@
+use super::{integrate_basis, Controller, Dop853, Solver, StepControl};
+use ndarray::Array1;
+use std::collections::VecDeque;
+
+/// Highest order of the Adams-Bashforth predictor
+const MAX_ORDER: usize = 12;
+/// Steps taken with DOP853 before switching to the multistep method, which then starts at order
+/// STARTUP_STEPS + 1
+const STARTUP_STEPS: usize = 7;
+
+/// Integrals over [0, 1] of the Lagrange basis polynomials through the given nodes. Nodes are
+/// epochs relative to the current one in units of the step size
+fn adams_weights(nodes: &[f64]) -> Vec<f64> {
+	(0..nodes.len())
+		.map(|j| integrate_basis(nodes, j, 0.0, 1.0, |_| 1.0))
+		.collect()
+}
+
+/// Variable-step, variable-order Adams-Bashforth-Moulton predictor-corrector (PECE with local
+/// extrapolation). Needs two derivative evaluations per step regardless of order, which makes it
+/// considerably cheaper than Runge-Kutta methods when the force model is expensive to evaluate
+pub struct Abm<F> {
+	f: F,
+	h: f64,
+	x: f64,
+	y: Array1<f64>,
+	xmax: f64,
+	atol: f64,
+	rtol: f64,
+	controller: Controller,
+	/// Current order of the predictor
+	order: usize,
+	steps_at_order: usize,
+	/// Previous epochs and derivatives, most recent (at x) first
+	history: VecDeque<(f64, Array1<f64>)>,
+	/// States computed by the startup method that have not been returned yet
+	pending: VecDeque<(f64, Array1<f64>)>,
+}
+
+impl<F> Abm<F> {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		atol: f64,
+		rtol: f64,
+		control: StepControl,
+	) -> Self {
+		Self {
+			f,
+			h: h.clamp(control.min_h, control.max_h),
+			x: x0,
+			y: y0.clone(),
+			xmax,
+			atol,
+			rtol,
+			controller: Controller {
+				control,
+				exponent: 1.0 / (STARTUP_STEPS as f64 + 2.0),
+				safety_factor: 0.8,
+				min_relative_dh: 0.2,
+				max_relative_dh: 2.0,
+			},
+			order: STARTUP_STEPS + 1,
+			steps_at_order: 0,
+			history: VecDeque::with_capacity(MAX_ORDER + 1),
+			pending: VecDeque::new(),
+		}
+	}
+}
+
+impl<F> Abm<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Take the first steps with DOP853 to build up the derivative history
+	fn start(&mut self) -> Result<(), String> {
+		self.history
+			.push_front((self.x, (self.f)(self.x, &self.y)?));
+
+		let f = &self.f;
+		let mut startup = Dop853::new(
+			f,
+			self.h,
+			self.x,
+			&self.y,
+			self.xmax,
+			self.atol,
+			self.rtol,
+			self.controller.control,
+		);
+		while self.history.len() <= STARTUP_STEPS {
+			let (x, y) = match startup.next_state()? {
+				Some(state) => state,
+				None => break,
+			};
+			self.h = x - self.history[0].0;
+			self.history.push_front((x, f(x, &y)?));
+			self.pending.push_back((x, y));
+		}
+		Ok(())
+	}
+
+	/// Predict and correct a step of size h at the current order. Returns the corrected state
+	/// and the normalized error estimates at the current and next lower order
+	fn step(&self, h: f64) -> Result<(Array1<f64>, f64, f64), String> {
+		let k = self.order;
+		let nodes = self
+			.history
+			.iter()
+			.take(k)
+			.map(|(x, _)| (x - self.x) / h)
+			.collect::<Vec<_>>();
+
+		let predict = |n: usize| {
+			let mut y = self.y.clone();
+			for (w, (_, f)) in adams_weights(&nodes[..n]).iter().zip(&self.history) {
+				y.scaled_add(h * w, f);
+			}
+			y
+		};
+
+		let y_p = predict(k);
+		let f_p = (self.f)(self.x + h, &y_p)?;
+
+		let corrector_nodes = std::iter::once(1.0)
+			.chain(nodes.iter().copied())
+			.collect::<Vec<_>>();
+		let weights = adams_weights(&corrector_nodes);
+		let mut y = self.y.clone();
+		y.scaled_add(h * weights[0], &f_p);
+		for (w, (_, f)) in weights[1..].iter().zip(&self.history) {
+			y.scaled_add(h * w, f);
+		}
+
+		let max_y_norm = y.dot(&y).sqrt().max(self.y.dot(&self.y).sqrt());
+		let tol = self.atol + self.rtol * max_y_norm;
+		let err_norm = |y_p: &Array1<f64>| {
+			let err = &y - y_p;
+			err.dot(&err).sqrt() / tol
+		};
+
+		let err = err_norm(&y_p);
+		let err_lower = if k > 1 {
+			err_norm(&predict(k - 1))
+		} else {
+			f64::INFINITY
+		};
+		Ok((y, err, err_lower))
+	}
+
+	/// Lower the order if that permits larger steps, and try raising it once the current order
+	/// has been kept for as many steps
+	fn select_order(&mut self, err: f64, err_lower: f64) {
+		let k = self.order;
+		self.steps_at_order += 1;
+		if k > 1 && err_lower.powf(1.0 / k as f64) < err.powf(1.0 / (k as f64 + 1.0)) {
+			self.order -= 1;
+			self.steps_at_order = 0;
+		} else if k < MAX_ORDER && self.steps_at_order >= k && self.history.len() > k {
+			self.order += 1;
+			self.steps_at_order = 0;
+		}
+		self.controller.exponent = 1.0 / (self.order as f64 + 1.0);
+	}
+}
+
+impl<F> Solver for Abm<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.history.is_empty() && self.x < self.xmax {
+			self.start()?;
+		}
+		if let Some((x, y)) = self.pending.pop_front() {
+			self.x = x;
+			self.y = y.clone();
+			return Ok(Some((x, y)));
+		}
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+
+		let errors = std::cell::Cell::new((0.0, 0.0));
+		let (x, y, h) = self
+			.controller
+			.advance("ABM", self.x, self.xmax, self.h, |h| {
+				let (y, err, err_lower) = self.step(h)?;
+				errors.set((err, err_lower));
+				Ok((y, err))
+			})?;
+
+		self.history.push_front((x, (self.f)(x, &y)?));
+		self.history.truncate(MAX_ORDER);
+		let (err, err_lower) = errors.get();
+		self.select_order(err, err_lower);
+
+		self.x = x;
+		self.y = y.clone();
+		self.h = h;
+		Ok(Some((x, y)))
+	}
+}
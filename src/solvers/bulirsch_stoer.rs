@@ -0,0 +1,174 @@
+use super::{Solver, StepControl};
+use ndarray::Array1;
+
+/// Highest column of the extrapolation table
+const MAX_COLUMN: usize = 8;
+
+/// Number of modified midpoint substeps in row j of the extrapolation table (Deuflhard sequence)
+fn substeps(j: usize) -> usize {
+	2 * (j + 1)
+}
+
+/// Gragg-Bulirsch-Stoer method - the modified midpoint rule at increasing numbers of substeps,
+/// extrapolated to zero substep size. Both the step size and the number of extrapolations
+/// (and thereby the order) are adapted to minimize the number of derivative evaluations
+pub struct BulirschStoer<F> {
+	f: F,
+	h: f64,
+	x: f64,
+	y: Array1<f64>,
+	xmax: f64,
+	atol: f64,
+	rtol: f64,
+	control: StepControl,
+	/// Column of the extrapolation table targeted for the next step
+	column: usize,
+}
+
+impl<F> BulirschStoer<F> {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		atol: f64,
+		rtol: f64,
+		control: StepControl,
+	) -> Self {
+		Self {
+			f,
+			h: h.clamp(control.min_h, control.max_h),
+			x: x0,
+			y: y0.clone(),
+			xmax,
+			atol,
+			rtol,
+			control,
+			column: 4,
+		}
+	}
+}
+
+impl<F> BulirschStoer<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Modified midpoint rule over a step of size h with n substeps, with Gragg's smoothing.
+	/// dy0 is the derivative at the start of the step
+	fn midpoint(&self, dy0: &Array1<f64>, h: f64, n: usize) -> Result<Array1<f64>, String> {
+		let sub_h = h / n as f64;
+		let mut z0 = self.y.clone();
+		let mut z1 = &self.y + &(dy0 * sub_h);
+		for m in 1..n {
+			let z2 = &z0 + &((self.f)(self.x + m as f64 * sub_h, &z1)? * (2.0 * sub_h));
+			z0 = z1;
+			z1 = z2;
+		}
+		let dy = (self.f)(self.x + h, &z1)?;
+		Ok((&z0 + &z1 + &(dy * sub_h)) * 0.5)
+	}
+}
+
+impl<F> Solver for BulirschStoer<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+
+		let dy0 = (self.f)(self.x, &self.y)?;
+		let mut rejections = 0;
+		loop {
+			// Don't step past the end - the shortened final step may fall below the minimum
+			let last_step = self.h >= self.xmax - self.x;
+			let h = if last_step {
+				self.xmax - self.x
+			} else {
+				self.h
+			};
+			let k = self.column;
+
+			// Optimal step size and derivative evaluations per unit step for each column
+			let mut optimal_h = [h; MAX_COLUMN + 1];
+			let mut work = [f64::INFINITY; MAX_COLUMN + 1];
+			let mut evaluations = 1;
+
+			let mut table: Vec<Vec<Array1<f64>>> = Vec::with_capacity(MAX_COLUMN + 1);
+			let mut converged = None;
+			let mut err_norm = f64::INFINITY;
+			for j in 0..=(k + 1).min(MAX_COLUMN) {
+				let n = substeps(j);
+				evaluations += n;
+
+				let mut row = vec![self.midpoint(&dy0, h, n)?];
+				for l in 1..=j {
+					let ratio = (n as f64 / substeps(j - l) as f64).powi(2);
+					let extrapolated =
+						&row[l - 1] + &((&row[l - 1] - &table[j - 1][l - 1]) / (ratio - 1.0));
+					row.push(extrapolated);
+				}
+
+				if j > 0 {
+					let err = &row[j] - &row[j - 1];
+					let max_y_norm = row[j].dot(&row[j]).sqrt().max(self.y.dot(&self.y).sqrt());
+					err_norm = err.dot(&err).sqrt() / (self.atol + self.rtol * max_y_norm);
+
+					let fac = 0.94 * (0.65 / err_norm).powf(1.0 / (2 * j + 1) as f64);
+					optimal_h[j] =
+						(h * fac.clamp(0.1, 4.0)).clamp(self.control.min_h, self.control.max_h);
+					work[j] = evaluations as f64 / optimal_h[j];
+
+					if j + 1 >= k && err_norm <= 1.0 {
+						converged = Some((j, row.swap_remove(j)));
+						break;
+					}
+				}
+				table.push(row);
+			}
+
+			if let Some((j, y)) = converged {
+				// Use one column less if that is cheaper, or try one more if that promises to be
+				let column = if j >= 2 && work[j - 1] < 0.8 * work[j] {
+					j - 1
+				} else if j < MAX_COLUMN && (j == 1 || work[j] < 0.9 * work[j - 1]) {
+					j + 1
+				} else {
+					j
+				};
+				self.h = if column > j {
+					let extra = substeps(column) as f64 / evaluations as f64;
+					(optimal_h[j] * (1.0 + extra)).min(self.control.max_h)
+				} else {
+					optimal_h[column]
+				};
+				self.column = column;
+
+				self.x = if last_step { self.xmax } else { self.x + h };
+				self.y = y.clone();
+				return Ok(Some((self.x, y)));
+			}
+
+			// Reject step and retry with the cheapest column computed so far
+			rejections += 1;
+			if h <= self.control.min_h {
+				return Err(format!(
+					"Bulirsch-Stoer: Error estimate {err_norm:.3e} exceeds tolerance at minimum step size {} (t = {})",
+					self.control.min_h, self.x
+				));
+			} else if rejections > self.control.max_rejections {
+				return Err(format!(
+					"Bulirsch-Stoer: Step at t = {} rejected {rejections} times in a row",
+					self.x
+				));
+			}
+			self.column = (1..=k.min(MAX_COLUMN))
+				.min_by(|&a, &b| work[a].partial_cmp(&work[b]).unwrap())
+				.unwrap();
+			self.h = optimal_h[self.column].min(h);
+		}
+	}
+}
@@ -0,0 +1,178 @@
+use super::Solver;
+use ndarray::Array1;
+
+/// Cubic Hermite interpolation of a single value at x between two epochs, given as (x, y, dy/dx)
+fn cubic_hermite((x0, y0, dy0): (f64, f64, f64), (x1, y1, dy1): (f64, f64, f64), x: f64) -> f64 {
+	let h = x1 - x0;
+	let t = (x - x0) / h;
+	let (t2, t3) = (t * t, t * t * t);
+	(2.0 * t3 - 3.0 * t2 + 1.0) * y0
+		+ (t3 - 2.0 * t2 + t) * h * dy0
+		+ (3.0 * t2 - 2.0 * t3) * y1
+		+ (t3 - t2) * h * dy1
+}
+
+/// Hermite interpolation of the state at x between two epochs, given as (x, y, dy/dx). The state
+/// is made up of blocks of block_size elements, each starting with a position and a velocity in 3
+/// dimensions. Positions are interpolated with quintic polynomials, as their first and second
+/// derivatives are known, and all other elements with cubic ones
+pub fn hermite(
+	start: (f64, &Array1<f64>, &Array1<f64>),
+	end: (f64, &Array1<f64>, &Array1<f64>),
+	x: f64,
+	block_size: usize,
+) -> Array1<f64> {
+	let (x0, y0, dy0) = start;
+	let (x1, y1, dy1) = end;
+	let h = x1 - x0;
+	let t = (x - x0) / h;
+	let (t2, t3) = (t * t, t * t * t);
+	let (t4, t5) = (t3 * t, t3 * t2);
+
+	let mut y = Array1::zeros(y0.len());
+	for i in 0..y.len() {
+		y[i] = if i % block_size < 3 {
+			(1.0 - 10.0 * t3 + 15.0 * t4 - 6.0 * t5) * y0[i]
+				+ (t - 6.0 * t3 + 8.0 * t4 - 3.0 * t5) * h * dy0[i]
+				+ 0.5 * (t2 - 3.0 * t3 + 3.0 * t4 - t5) * h * h * dy0[i + 3]
+				+ (10.0 * t3 - 15.0 * t4 + 6.0 * t5) * y1[i]
+				+ (-4.0 * t3 + 7.0 * t4 - 3.0 * t5) * h * dy1[i]
+				+ 0.5 * (t3 - 2.0 * t4 + t5) * h * h * dy1[i + 3]
+		} else {
+			cubic_hermite((x0, y0[i], dy0[i]), (x1, y1[i], dy1[i]), x)
+		};
+	}
+	y
+}
+
+/// State of the wrapped solver at the end of a step, with its derivative once it is needed
+struct Node {
+	x: f64,
+	y: Array1<f64>,
+	dy: Option<Array1<f64>>,
+}
+
+/// Dense output on a uniform grid of epochs origin + k * step, independent of the steps taken by
+/// the wrapped solver. States in between steps are taken from the solver's own interpolant where
+/// it has one. Otherwise they are interpolated from the states and derivatives at both ends of the
+/// step (see hermite), at the cost of one more evaluation of f per step. The final state at xmax
+/// is always returned
+pub struct Dense<'a, F> {
+	solver: Box<dyn Solver + 'a>,
+	f: F,
+	origin: f64,
+	step: f64,
+	xmax: f64,
+	block_size: usize,
+	/// Index of the next grid epoch to return
+	k: f64,
+	start: Node,
+	end: Option<Node>,
+	finished: bool,
+}
+
+impl<'a, F> Dense<'a, F> {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		solver: Box<dyn Solver + 'a>,
+		f: F,
+		origin: f64,
+		step: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		block_size: usize,
+	) -> Self {
+		Self {
+			solver,
+			f,
+			origin,
+			step,
+			xmax,
+			block_size,
+			// The first grid epoch after x0, skipping any that x0 only misses due to rounding
+			k: ((x0 - origin) / step + 1e-9).floor() + 1.0,
+			start: Node {
+				x: x0,
+				y: y0.clone(),
+				dy: None,
+			},
+			end: None,
+			finished: false,
+		}
+	}
+}
+
+impl<'a, F> Dense<'a, F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Hermite interpolation of the state at x within the last step
+	fn hermite(&mut self, x: f64) -> Result<Array1<f64>, String> {
+		let end = self.end.as_mut().unwrap();
+		for node in [&mut self.start, end] {
+			if node.dy.is_none() {
+				node.dy = Some((self.f)(node.x, &node.y)?);
+			}
+		}
+		let (start, end) = (&self.start, self.end.as_ref().unwrap());
+		Ok(hermite(
+			(start.x, &start.y, start.dy.as_ref().unwrap()),
+			(end.x, &end.y, end.dy.as_ref().unwrap()),
+			x,
+			self.block_size,
+		))
+	}
+}
+
+impl<'a, F> Solver for Dense<'a, F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		while !self.finished {
+			let grid_x = self.origin + self.k * self.step;
+			// Don't return a grid epoch that only precedes xmax due to rounding
+			let x = if grid_x >= self.xmax - 1e-9 * self.step {
+				self.xmax
+			} else {
+				grid_x
+			};
+
+			if let Some(end) = &self.end {
+				if x == end.x {
+					self.k += 1.0;
+					self.finished = x == self.xmax;
+					return Ok(Some((x, end.y.clone())));
+				} else if x < end.x {
+					let y = match self.solver.interpolate(x) {
+						Some(y) => y,
+						None => self.hermite(x)?,
+					};
+					self.k += 1.0;
+					return Ok(Some((x, y)));
+				}
+			}
+
+			match self.solver.next_state()? {
+				Some((x, y)) => {
+					if let Some(end) = self.end.take() {
+						self.start = end;
+					}
+					self.end = Some(Node { x, y, dy: None });
+				}
+				None => self.finished = true,
+			}
+		}
+		Ok(None)
+	}
+
+	fn interpolate(&self, x: f64) -> Option<Array1<f64>> {
+		// The wrapped solver's continuous extension only covers its last step
+		let end = self.end.as_ref()?;
+		if x < self.start.x || x > end.x {
+			return None;
+		}
+		self.solver.interpolate(x)
+	}
+}
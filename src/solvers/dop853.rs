@@ -0,0 +1,174 @@
+// Coefficients are kept at their published precision
+#![allow(clippy::excessive_precision)]
+
+use super::{Controller, Solver, StepControl};
+use ndarray::Array1;
+
+// Coefficients of the Dormand-Prince 8(5,3) method as given by Hairer, Norsett & Wanner
+#[rustfmt::skip]
+const C: [f64; 12] = [
+	0.0,
+	0.526001519587677318785587544488e-1,
+	0.789002279381515978178381316732e-1,
+	0.118350341907227396726757197510e0,
+	0.281649658092772603273242802490e0,
+	0.333333333333333333333333333333e0,
+	0.25e0,
+	0.307692307692307692307692307692e0,
+	0.651282051282051282051282051282e0,
+	0.6e0,
+	0.857142857142857142857142857142e0,
+	1.0,
+];
+
+#[rustfmt::skip]
+const A: [&[f64]; 12] = [
+	&[],
+	&[5.26001519587677318785587544488e-2],
+	&[1.97250569845378994544595329183e-2, 5.91751709536136983633785987549e-2],
+	&[2.95875854768068491816892993775e-2, 0.0, 8.87627564304205475450678981324e-2],
+	&[2.41365134159266685502369798665e-1, 0.0, -8.84549479328286085344864962717e-1, 9.24834003261792003115737966543e-1],
+	&[3.7037037037037037037037037037e-2, 0.0, 0.0, 1.70828608729473871279604482173e-1, 1.25467687566822425016691814123e-1],
+	&[3.7109375e-2, 0.0, 0.0, 1.70252211019544039314978060272e-1, 6.02165389804559606850219397283e-2, -1.7578125e-2],
+	&[3.70920001185047927108779319836e-2, 0.0, 0.0, 1.70383925712239993810214054705e-1, 1.07262030446373284651809199168e-1,
+		-1.53194377486244017527936158236e-2, 8.27378916381402288758473766002e-3],
+	&[6.24110958716075717114429577812e-1, 0.0, 0.0, -3.36089262944694129406857109825e0, -8.68219346841726006818189891453e-1,
+		2.75920996994467083049415600797e1, 2.01540675504778934086186788979e1, -4.34898841810699588477366255144e1],
+	&[4.77662536438264365890433908527e-1, 0.0, 0.0, -2.48811461997166764192642586468e0, -5.90290826836842996371446475743e-1,
+		2.12300514481811942347288949897e1, 1.52792336328824235832596922938e1, -3.32882109689848629194453265587e1,
+		-2.03312017085086261358222928593e-2],
+	&[-9.3714243008598732571704021658e-1, 0.0, 0.0, 5.18637242884406370830023853209e0, 1.09143734899672957818500254654e0,
+		-8.14978701074692612513997267357e0, -1.85200656599969598641566180701e1, 2.27394870993505042818970056734e1,
+		2.49360555267965238987089396762e0, -3.0467644718982195003823669022e0],
+	&[2.27331014751653820792359768449e0, 0.0, 0.0, -1.05344954667372501984066689879e1, -2.00087205822486249909675718444e0,
+		-1.79589318631187989172765950534e1, 2.79488845294199600508499808837e1, -2.85899827713502369474065508674e0,
+		-8.87285693353062954433549289258e0, 1.23605671757943030647266201528e1, 6.43392746015763530355970484046e-1],
+];
+
+/// Weights of the 8th order solution
+#[rustfmt::skip]
+const B: [f64; 12] = [
+	5.42937341165687622380535766363e-2, 0.0, 0.0, 0.0, 0.0,
+	4.45031289275240888144113950566e0, 1.89151789931450038304281599044e0, -5.8012039600105847814672114227e0,
+	3.1116436695781989440891606237e-1, -1.52160949662516078556178806805e-1, 2.01365400804030348374776537501e-1,
+	4.47106157277725905176885569043e-2,
+];
+
+/// Differences between the 8th order weights and those of the embedded 5th order solution
+#[rustfmt::skip]
+const ER: [f64; 12] = [
+	0.1312004499419488073250102996e-1, 0.0, 0.0, 0.0, 0.0,
+	-0.1225156446376204440720569753e1, -0.4957589496572501915214079952e0, 0.1664377182454986536961530415e1,
+	-0.3503288487499736816886487290e0, 0.3341791187130174790297318841e0, 0.8192320648511571246570742613e-1,
+	-0.2235530786388629525884427845e-1,
+];
+
+/// Weights of the embedded 3rd order solution (stages 1, 9 and 12)
+const BHH: [f64; 3] = [
+	0.244094488188976377952755905512e0,
+	0.733846688281611857341361741547e0,
+	0.220588235294117647058823529412e-1,
+];
+
+/// Dormand-Prince 8th order method with 5th and 3rd order error estimators
+pub struct Dop853<F> {
+	f: F,
+	h: f64,
+	x: f64,
+	y: Array1<f64>,
+	xmax: f64,
+	atol: f64,
+	rtol: f64,
+	controller: Controller,
+}
+
+impl<F> Dop853<F> {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		atol: f64,
+		rtol: f64,
+		control: StepControl,
+	) -> Self {
+		Self {
+			f,
+			h: h.clamp(control.min_h, control.max_h),
+			x: x0,
+			y: y0.clone(),
+			xmax,
+			atol,
+			rtol,
+			controller: Controller {
+				control,
+				exponent: 1.0 / 8.0,
+				safety_factor: 0.9,
+				min_relative_dh: 1.0 / 3.0,
+				max_relative_dh: 6.0,
+			},
+		}
+	}
+}
+
+impl<F> Dop853<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Take a single step of size h. Returns the new state and the normalized error estimate
+	fn step(&self, h: f64) -> Result<(Array1<f64>, f64), String> {
+		let mut k: Vec<Array1<f64>> = Vec::with_capacity(12);
+		for (c, a) in C.iter().zip(A) {
+			let mut y = self.y.clone();
+			for (a, k) in a.iter().zip(&k).filter(|(&a, _)| a != 0.0) {
+				y.scaled_add(h * a, k);
+			}
+			k.push((self.f)(self.x + c * h, &y)?);
+		}
+
+		let mut y = self.y.clone();
+		let mut err5 = Array1::zeros(y.len());
+		for ((b, er), k) in B.iter().zip(&ER).zip(&k).filter(|((&b, _), _)| b != 0.0) {
+			y.scaled_add(h * b, k);
+			err5.scaled_add(*er, k);
+		}
+
+		// 3rd order estimate, expressed relative to the 8th order increment
+		let mut err3 = (&y - &self.y) / h;
+		err3.scaled_add(-BHH[0], &k[0]);
+		err3.scaled_add(-BHH[1], &k[8]);
+		err3.scaled_add(-BHH[2], &k[11]);
+
+		let max_y_norm = y.dot(&y).sqrt().max(self.y.dot(&self.y).sqrt());
+		let tol = self.atol + self.rtol * max_y_norm;
+		let err5_sq = err5.dot(&err5) / (tol * tol);
+		let err3_sq = err3.dot(&err3) / (tol * tol);
+
+		let denominator = match err5_sq + 0.01 * err3_sq {
+			d if d > 0.0 => d,
+			_ => 1.0,
+		};
+		Ok((y, h.abs() * err5_sq / denominator.sqrt()))
+	}
+}
+
+impl<F> Solver for Dop853<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+
+		let (x, y, h) = self
+			.controller
+			.advance("DOP853", self.x, self.xmax, self.h, |h| self.step(h))?;
+		self.x = x;
+		self.y = y.clone();
+		self.h = h;
+		Ok(Some((x, y)))
+	}
+}
@@ -0,0 +1,315 @@
+use super::{integrate_basis, step_fns, Solver};
+use ndarray::Array1;
+use std::collections::VecDeque;
+
+/// Order of the method. Each step uses the derivatives at the last ORDER + 1 epochs
+const ORDER: usize = 8;
+const POINTS: usize = ORDER + 1;
+/// Limit on corrector iterations refining the RK4 startup states
+const MAX_STARTUP_ITERATIONS: usize = 20;
+
+type Row = [f64; POINTS];
+
+/// Ordinate form coefficients of the summed Adams and Stormer-Cowell formulas, with the first and
+/// second sums s and S defined as in Berry & Healy (2004). Given derivatives a_0..a_8 at the epochs
+/// of the window, velocities and positions at epoch j are v_j / h = s_j + b_j . a and
+/// r_j / h^2 = S_j + a_j . a
+struct Coefficients {
+	b: [Row; POINTS],
+	a: [Row; POINTS],
+	/// Predictor for the next epoch, v_9 / h = s_8 + a_8 / 2 + b_pred . a
+	b_pred: Row,
+	/// Predictor for the next epoch, r_9 / h^2 = S_9 + a_pred . a
+	a_pred: Row,
+}
+
+impl Coefficients {
+	fn new() -> Self {
+		let nodes = (0..POINTS).map(|k| k as f64).collect::<Vec<_>>();
+		let row = |coef: &dyn Fn(usize) -> f64| {
+			let mut row = [0f64; POINTS];
+			row.iter_mut().enumerate().for_each(|(k, c)| *c = coef(k));
+			row
+		};
+		let integral = |from: f64, to: f64| row(&|k| integrate_basis(&nodes, k, from, to, |_| 1.0));
+		let moment =
+			|from: f64, to: f64| row(&|k| integrate_basis(&nodes, k, from, to, |t| to - t));
+		let unit = |j: usize| row(&|k| if k == j { 1.0 } else { 0.0 });
+		let add = |x: Row, y: Row, factor: f64| row(&|k| x[k] + factor * y[k]);
+
+		// Backward difference of order m at the last epoch
+		let difference = |m: usize| {
+			let mut row = [0f64; POINTS];
+			let mut binomial = 1f64;
+			for j in 0..=m {
+				row[ORDER - j] = if j % 2 == 0 { binomial } else { -binomial };
+				binomial *= (m - j) as f64 / (j + 1) as f64;
+			}
+			row
+		};
+
+		// Series of the Adams-Moulton operator z / -ln(1 - z) and its square, the Stormer-Cowell
+		// operator, in powers of the backward difference
+		let mut gamma = Vec::with_capacity(ORDER + 3);
+		for i in 0..ORDER + 3 {
+			let g = -(1..=i).map(|k| gamma[i - k] / (k + 1) as f64).sum::<f64>();
+			gamma.push(if i == 0 { 1f64 } else { g });
+		}
+		let sigma = (0..gamma.len())
+			.map(|i| (0..=i).map(|k| gamma[k] * gamma[i - k]).sum::<f64>())
+			.collect::<Vec<_>>();
+
+		let mut b = [[0f64; POINTS]; POINTS];
+		let mut a = [[0f64; POINTS]; POINTS];
+		for (i, g) in gamma.iter().enumerate().take(ORDER + 2).skip(2) {
+			b[ORDER] = add(b[ORDER], difference(i - 1), *g);
+		}
+		for (i, s) in sigma.iter().enumerate().skip(2) {
+			a[ORDER] = add(a[ORDER], difference(i - 2), *s);
+		}
+
+		// Earlier epochs follow from the recurrences of the sums, s_j - s_{j-1} = (a_{j-1} + a_j) / 2
+		// and S_j - S_{j-1} = s_{j-1} + a_{j-1} / 2
+		for j in (1..POINTS).rev() {
+			let (from, to) = ((j - 1) as f64, j as f64);
+			let trapezoid = add(add(integral(from, to), unit(j - 1), -0.5), unit(j), -0.5);
+			b[j - 1] = add(b[j], trapezoid, -1.0);
+			let step = add(add(b[j - 1], moment(from, to), 1.0), unit(j - 1), -0.5);
+			a[j - 1] = add(a[j], step, -1.0);
+		}
+
+		let (from, to) = (ORDER as f64, POINTS as f64);
+		let b_pred = add(add(b[ORDER], integral(from, to), 1.0), unit(ORDER), -0.5);
+		let a_pred = add(
+			add(add(a[ORDER], b[ORDER], 1.0), moment(from, to), 1.0),
+			unit(ORDER),
+			-0.5,
+		);
+
+		Self {
+			b,
+			a,
+			b_pred,
+			a_pred,
+		}
+	}
+}
+
+/// Fixed-step 8th order Gauss-Jackson method, integrating positions directly from accelerations.
+/// The state is made up of blocks of block_size elements, each starting with a position and a
+/// velocity in 3 dimensions. Any further elements of a block are integrated as a first order ODE.
+/// The method starts itself with ORDER full RK4 steps, so the interval from x0 to xmax must span at
+/// least ORDER steps. The final step is shortened to end at xmax
+pub struct GaussJackson<F> {
+	f: F,
+	h: f64,
+	x0: f64,
+	steps: usize,
+	x: f64,
+	y: Array1<f64>,
+	xmax: f64,
+	/// Indices of position elements in the state vector. The velocity belonging to position i is
+	/// at i + 3
+	positions: Vec<usize>,
+	coefficients: Coefficients,
+	/// Derivatives at the last POINTS epochs, oldest first
+	window: VecDeque<Array1<f64>>,
+	first_sum: Array1<f64>,
+	second_sum: Array1<f64>,
+	/// States computed during startup that have not been returned yet
+	pending: VecDeque<(f64, Array1<f64>)>,
+}
+
+impl<F> GaussJackson<F> {
+	pub fn new(f: F, h: f64, x0: f64, y0: &Array1<f64>, xmax: f64, block_size: usize) -> Self {
+		Self {
+			f,
+			h,
+			x0,
+			steps: 0,
+			x: x0,
+			y: y0.clone(),
+			xmax,
+			positions: (0..y0.len()).filter(|i| i % block_size < 3).collect(),
+			coefficients: Coefficients::new(),
+			window: VecDeque::with_capacity(POINTS),
+			first_sum: Array1::zeros(0),
+			second_sum: Array1::zeros(0),
+			pending: VecDeque::new(),
+		}
+	}
+
+	/// Split a state into the quantities obtained by integrating its derivative once and twice.
+	/// Positions are placed at the index of the corresponding acceleration in the latter
+	fn split(&self, y: &Array1<f64>) -> (Array1<f64>, Array1<f64>) {
+		let mut second = Array1::zeros(y.len());
+		for &i in &self.positions {
+			second[i + 3] = y[i];
+		}
+		(y.clone(), second)
+	}
+
+	/// Inverse of split
+	fn join(&self, first: Array1<f64>, second: &Array1<f64>) -> Array1<f64> {
+		let mut y = first;
+		for &i in &self.positions {
+			y[i] = second[i + 3];
+		}
+		y
+	}
+
+	/// Linear combination of the derivatives in the window
+	fn combine(&self, row: &Row) -> Array1<f64> {
+		let mut sum = Array1::zeros(self.y.len());
+		for (c, d) in row.iter().zip(&self.window) {
+			sum.scaled_add(*c, d);
+		}
+		sum
+	}
+}
+
+impl<F> GaussJackson<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Take the first ORDER steps with RK4, then iterate the corrector on them until they
+	/// converge to the accuracy of the method
+	fn start(&mut self) -> Result<(), String> {
+		let h = self.h;
+		let mut ys = vec![self.y.clone()];
+		for n in 0..ORDER {
+			let x = self.x0 + n as f64 * h;
+			ys.push(step_fns::rk4(|x, y| (self.f)(x, y), x, &ys[n], h)?.1);
+		}
+		let xs = (0..POINTS)
+			.map(|n| self.x0 + n as f64 * h)
+			.collect::<Vec<_>>();
+		self.window = xs
+			.iter()
+			.zip(&ys)
+			.map(|(x, y)| (self.f)(*x, y))
+			.collect::<Result<_, _>>()?;
+
+		for _ in 0..MAX_STARTUP_ITERATIONS {
+			let c = &self.coefficients;
+			let (first, second) = self.split(&ys[0]);
+			self.first_sum = &first / h - self.combine(&c.b[0]);
+			self.second_sum = &second / (h * h) - self.combine(&c.a[0]);
+
+			let mut converged = true;
+			for (n, y_n) in ys.iter_mut().enumerate().skip(1) {
+				self.second_sum = &self.second_sum + &self.first_sum + &self.window[n - 1] * 0.5;
+				self.first_sum = &self.first_sum + (&self.window[n - 1] + &self.window[n]) * 0.5;
+				let y = self.join(
+					(&self.first_sum + self.combine(&c.b[n])) * h,
+					&((&self.second_sum + self.combine(&c.a[n])) * (h * h)),
+				);
+				let change = &y - &*y_n;
+				converged &= change.dot(&change) <= 1e-28 * y.dot(&y);
+				*y_n = y;
+			}
+			if converged {
+				break;
+			}
+			for n in 1..POINTS {
+				self.window[n] = (self.f)(xs[n], &ys[n])?;
+			}
+		}
+
+		self.steps = ORDER;
+		self.x = xs[ORDER];
+		self.y = ys[ORDER].clone();
+		self.pending = xs.into_iter().zip(ys).skip(1).collect();
+		Ok(())
+	}
+
+	/// Predict, evaluate, correct and evaluate the state one full step ahead
+	fn step(&mut self) -> Result<(f64, Array1<f64>), String> {
+		let h = self.h;
+		let x = self.x0 + (self.steps + 1) as f64 * h;
+		let c = &self.coefficients;
+
+		let second_sum = &self.second_sum + &self.first_sum + &self.window[ORDER] * 0.5;
+		let predicted = self.join(
+			(&self.first_sum + &self.window[ORDER] * 0.5 + self.combine(&c.b_pred)) * h,
+			&((&second_sum + self.combine(&c.a_pred)) * (h * h)),
+		);
+		self.window.pop_front();
+		self.window.push_back((self.f)(x, &predicted)?);
+
+		let first_sum = &self.first_sum + (&self.window[ORDER - 1] + &self.window[ORDER]) * 0.5;
+		let y = self.join(
+			(&first_sum + self.combine(&c.b[ORDER])) * h,
+			&((&second_sum + self.combine(&c.a[ORDER])) * (h * h)),
+		);
+		self.window[ORDER] = (self.f)(x, &y)?;
+
+		self.first_sum = &self.first_sum + (&self.window[ORDER - 1] + &self.window[ORDER]) * 0.5;
+		self.second_sum = second_sum;
+		self.steps += 1;
+		Ok((x, y))
+	}
+
+	/// Integrate the interpolated derivatives over a partial step to xmax
+	fn final_step(&self) -> (f64, Array1<f64>) {
+		let h = self.h;
+		let theta = (self.xmax - self.x) / h;
+		let nodes = (0..POINTS).map(|k| k as f64).collect::<Vec<_>>();
+		let (from, to) = (ORDER as f64, ORDER as f64 + theta);
+
+		let mut first_integral = [0f64; POINTS];
+		let mut second_integral = [0f64; POINTS];
+		for k in 0..POINTS {
+			first_integral[k] = integrate_basis(&nodes, k, from, to, |_| 1.0);
+			second_integral[k] = integrate_basis(&nodes, k, from, to, |t| to - t);
+		}
+
+		let (first, second) = self.split(&self.y);
+		let second = second + &first * (theta * h) + self.combine(&second_integral) * (h * h);
+		let first = first + self.combine(&first_integral) * h;
+		(self.xmax, self.join(first, &second))
+	}
+}
+
+impl<F> Solver for GaussJackson<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.window.is_empty() && self.x < self.xmax {
+			if self.xmax - self.x0 < ORDER as f64 * self.h {
+				return Err(format!(
+					"gauss-jackson: Starting the method takes {ORDER} steps of size {}, but the integration interval only spans {} - Use a smaller timestep",
+					self.h,
+					self.xmax - self.x0
+				));
+			}
+			self.start()?;
+		}
+		if let Some(state) = self.pending.pop_front() {
+			return Ok(Some(state));
+		}
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+
+		let next_x = self.x0 + (self.steps + 1) as f64 * self.h;
+		let (x, y) = if next_x > self.xmax + 1e-9 * self.h {
+			self.final_step()
+		} else {
+			let (x, y) = self.step()?;
+			// Don't leave a negligible remainder due to rounding
+			(
+				if self.xmax - x < 1e-9 * self.h {
+					self.xmax
+				} else {
+					x
+				},
+				y,
+			)
+		};
+		self.x = x;
+		self.y = y.clone();
+		Ok(Some((x, y)))
+	}
+}
@@ -0,0 +1,265 @@
+// Coefficients are kept at their published precision
+#![allow(clippy::excessive_precision)]
+
+use super::{Solver, StepControl};
+use ndarray::Array1;
+
+/// Gauss-Radau substep spacings as fractions of a step, following the initial one at 0
+const SPACINGS: [f64; 7] = [
+	0.0562625605369221464656521910318,
+	0.180240691736892364987579942780,
+	0.352624717113169637373907769648,
+	0.547153626330555383001448554766,
+	0.734210177215410531523210605558,
+	0.885320946839095768090359771030,
+	0.977520613561287501891174488626,
+];
+/// Target magnitude of the highest order term relative to the accelerations
+const EPSILON: f64 = 1e-9;
+/// Steps shrinking below this fraction of the attempted step size are rejected
+const SAFETY_FACTOR: f64 = 0.25;
+/// Limit on predictor-corrector iterations per step
+const MAX_ITERATIONS: usize = 12;
+
+/// Coefficients of the acceleration's expansion a(t) = a0 + b0 t + b1 t^2 + ... + b6 t^7 over a
+/// step, with t as a fraction of the step
+type Coefficients = [Array1<f64>; 7];
+
+/// IAS15 (Rein & Spiegel 2015) - 15th order implicit Gauss-Radau integrator with step sizes
+/// chosen such that the highest order term of the acceleration's expansion stays negligible.
+/// Handles close encounters at machine precision without tuning of the initial step size.
+///
+/// The state is made up of blocks of block_size elements, each starting with a position and a
+/// velocity in 3 dimensions. Any further elements of a block are integrated as a first order ODE
+pub struct Ias15<F> {
+	f: F,
+	h: f64,
+	x: f64,
+	y: Array1<f64>,
+	xmax: f64,
+	control: StepControl,
+	/// Indices of position elements in the state vector. The velocity belonging to position i is
+	/// at i + 3
+	positions: Vec<usize>,
+	/// c[j][k] is the coefficient of t^(k + 1) in t (t - h1) ... (t - hj), converting the Newton
+	/// form coefficients g to b
+	c: [[f64; 7]; 7],
+	/// Inverse of c, converting b to g
+	d: [[f64; 7]; 7],
+	/// Coefficients predicted for the current step, and the corrections the last step's
+	/// predictor-corrector iteration made to its prediction
+	b: Coefficients,
+	corrections: Coefficients,
+	/// Converged coefficients and size of the last accepted step
+	last: Option<(Coefficients, f64)>,
+}
+
+impl<F> Ias15<F> {
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		control: StepControl,
+		block_size: usize,
+	) -> Self {
+		let mut c = [[0f64; 7]; 7];
+		c[0][0] = 1.0;
+		for j in 1..7 {
+			for k in 0..=j {
+				let shifted = if k > 0 { c[j - 1][k - 1] } else { 0.0 };
+				c[j][k] = shifted - SPACINGS[j - 1] * c[j - 1][k];
+			}
+		}
+
+		// Invert the unit upper triangular c by back substitution
+		let mut d = [[0f64; 7]; 7];
+		for (k, row) in d.iter_mut().enumerate() {
+			row[k] = 1.0;
+			for j in (0..k).rev() {
+				let value = -(j + 1..=k).map(|m| row[m] * c[m][j]).sum::<f64>();
+				row[j] = value;
+			}
+		}
+
+		let zeros = || std::array::from_fn(|_| Array1::zeros(y0.len()));
+		Self {
+			f,
+			h: h.clamp(control.min_h, control.max_h),
+			x: x0,
+			y: y0.clone(),
+			xmax,
+			control,
+			positions: (0..y0.len()).filter(|i| i % block_size < 3).collect(),
+			c,
+			d,
+			b: zeros(),
+			corrections: zeros(),
+			last: None,
+		}
+	}
+
+	/// State at fraction t of a step of size h, given the derivative dy0 at its start
+	fn state_at(&self, dy0: &Array1<f64>, b: &Coefficients, h: f64, t: f64) -> Array1<f64> {
+		let mut first = dy0 * t;
+		let mut second = dy0 * (t * t / 2.0);
+		for (k, b) in b.iter().enumerate() {
+			let k = k as f64;
+			first.scaled_add(t.powf(k + 2.0) / (k + 2.0), b);
+			second.scaled_add(t.powf(k + 3.0) / ((k + 2.0) * (k + 3.0)), b);
+		}
+
+		let mut y = &self.y + &(first * h);
+		for &i in &self.positions {
+			y[i] = self.y[i] + self.y[i + 3] * t * h + second[i + 3] * h * h;
+		}
+		y
+	}
+
+	/// Largest magnitude of the accelerations in a derivative
+	fn max_acceleration(&self, dy: &Array1<f64>) -> f64 {
+		self.positions
+			.iter()
+			.map(|&i| dy[i + 3].abs())
+			.fold(0f64, f64::max)
+	}
+
+	/// Expand the last step's coefficients about the start of the next one, whose size is ratio
+	/// times the last one's, and reapply the last step's corrections
+	fn predict(&mut self, b: &Coefficients, ratio: f64) {
+		if ratio > 20.0 {
+			// Too far to extrapolate
+			self.b.iter_mut().for_each(|b| b.fill(0.0));
+			self.corrections.iter_mut().for_each(|b| b.fill(0.0));
+			return;
+		}
+		for k in 0..7 {
+			let mut binomial = 1f64;
+			let mut predicted = Array1::zeros(self.y.len());
+			for (j, b) in b.iter().enumerate().skip(k) {
+				predicted.scaled_add(binomial, b);
+				binomial *= (j + 2) as f64 / (j + 1 - k) as f64;
+			}
+			self.b[k] = predicted * ratio.powi(k as i32 + 1) + &self.corrections[k];
+		}
+	}
+}
+
+impl<F> Ias15<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Iterate the predictor-corrector for a step of size h until the coefficients converge.
+	/// Returns the converged coefficients and the derivative at the last substep
+	fn iterate(&self, dy0: &Array1<f64>, h: f64) -> Result<(Coefficients, Array1<f64>), String> {
+		let mut b = self.b.clone();
+		let mut g: Coefficients = std::array::from_fn(|j| {
+			(j..7).fold(Array1::zeros(dy0.len()), |g, k| g + &b[k] * self.d[k][j])
+		});
+
+		let mut last_error = f64::INFINITY;
+		let mut dy = dy0.clone();
+		for iteration in 0..MAX_ITERATIONS {
+			let mut b6_change = 0f64;
+			for n in 0..7 {
+				let t = SPACINGS[n];
+				dy = (self.f)(self.x + t * h, &self.state_at(dy0, &b, h, t))?;
+
+				// Divided difference of the derivatives at the substeps so far
+				let mut g_new = (&dy - dy0) / t;
+				for m in 0..n {
+					g_new = (g_new - &g[m]) / (t - SPACINGS[m]);
+				}
+				let delta = &g_new - &g[n];
+				g[n] = g_new;
+				for (k, b) in b.iter_mut().enumerate().take(n + 1) {
+					b.scaled_add(self.c[n][k], &delta);
+				}
+				if n == 6 {
+					b6_change = self.max_acceleration(&delta);
+				}
+			}
+
+			let error = b6_change / self.max_acceleration(&dy);
+			if error < 1e-16 || (iteration > 1 && error >= last_error) {
+				break;
+			}
+			last_error = error;
+		}
+		Ok((b, dy))
+	}
+}
+
+impl<F> Solver for Ias15<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+
+		let dy0 = (self.f)(self.x, &self.y)?;
+		let mut rejections = 0;
+		loop {
+			// Don't step past the end - the shortened final step may fall below the minimum
+			let last_step = self.h >= self.xmax - self.x;
+			let h = if last_step {
+				self.xmax - self.x
+			} else {
+				self.h
+			};
+
+			let (b, dy) = self.iterate(&dy0, h)?;
+			let max_b6 = self.max_acceleration(&b[6]);
+			let max_acceleration = self.max_acceleration(&dy);
+			let new_h = if max_b6 > 0.0 && max_acceleration > 0.0 {
+				h * (EPSILON / (max_b6 / max_acceleration)).powf(1.0 / 7.0)
+			} else {
+				h / SAFETY_FACTOR
+			};
+
+			if new_h < SAFETY_FACTOR * h {
+				rejections += 1;
+				if h <= self.control.min_h {
+					return Err(format!(
+						"IAS15: Highest order term exceeds tolerance at minimum step size {} (t = {})",
+						self.control.min_h, self.x
+					));
+				} else if rejections > self.control.max_rejections {
+					return Err(format!(
+						"IAS15: Step at t = {} rejected {rejections} times in a row",
+						self.x
+					));
+				}
+				self.h = new_h.max(self.control.min_h);
+				match self.last.take() {
+					Some((b, last_h)) => {
+						self.predict(&b, self.h / last_h);
+						self.last = Some((b, last_h));
+					}
+					None => self.b.iter_mut().for_each(|b| b.fill(0.0)),
+				}
+				continue;
+			}
+
+			// Accept the step
+			let y = self.state_at(&dy0, &b, h, 1.0);
+			for (correction, (b, predicted)) in
+				self.corrections.iter_mut().zip(b.iter().zip(&self.b))
+			{
+				*correction = b - predicted;
+			}
+			self.h = new_h
+				.min(h / SAFETY_FACTOR)
+				.clamp(self.control.min_h, self.control.max_h);
+			self.predict(&b, self.h / h);
+			self.last = Some((b, h));
+
+			self.x = if last_step { self.xmax } else { self.x + h };
+			self.y = y.clone();
+			return Ok(Some((self.x, y)));
+		}
+	}
+}
@@ -0,0 +1,124 @@
+use super::{Controller, Solver, StepControl};
+use ndarray::Array1;
+
+// Coefficients of Fehlberg's 7(8) method (NASA TR R-287, 1968)
+#[rustfmt::skip]
+const C: [f64; 13] = [
+	0.0, 2.0/27.0, 1.0/9.0, 1.0/6.0, 5.0/12.0, 1.0/2.0, 5.0/6.0, 1.0/6.0, 2.0/3.0, 1.0/3.0, 1.0, 0.0, 1.0,
+];
+
+#[rustfmt::skip]
+const A: [&[f64]; 13] = [
+	&[],
+	&[2.0/27.0],
+	&[1.0/36.0, 1.0/12.0],
+	&[1.0/24.0, 0.0, 1.0/8.0],
+	&[5.0/12.0, 0.0, -25.0/16.0, 25.0/16.0],
+	&[1.0/20.0, 0.0, 0.0, 1.0/4.0, 1.0/5.0],
+	&[-25.0/108.0, 0.0, 0.0, 125.0/108.0, -65.0/27.0, 125.0/54.0],
+	&[31.0/300.0, 0.0, 0.0, 0.0, 61.0/225.0, -2.0/9.0, 13.0/900.0],
+	&[2.0, 0.0, 0.0, -53.0/6.0, 704.0/45.0, -107.0/9.0, 67.0/90.0, 3.0],
+	&[-91.0/108.0, 0.0, 0.0, 23.0/108.0, -976.0/135.0, 311.0/54.0, -19.0/60.0, 17.0/6.0, -1.0/12.0],
+	&[2383.0/4100.0, 0.0, 0.0, -341.0/164.0, 4496.0/1025.0, -301.0/82.0, 2133.0/4100.0, 45.0/82.0, 45.0/164.0, 18.0/41.0],
+	&[3.0/205.0, 0.0, 0.0, 0.0, 0.0, -6.0/41.0, -3.0/205.0, -3.0/41.0, 3.0/41.0, 6.0/41.0, 0.0],
+	&[-1777.0/4100.0, 0.0, 0.0, -341.0/164.0, 4496.0/1025.0, -289.0/82.0, 2193.0/4100.0, 51.0/82.0, 33.0/164.0, 12.0/41.0, 0.0, 1.0],
+];
+
+/// Weights of the 7th order solution, which is the one propagated
+#[rustfmt::skip]
+const B: [f64; 13] = [
+	41.0/840.0, 0.0, 0.0, 0.0, 0.0, 34.0/105.0, 9.0/35.0, 9.0/35.0, 9.0/280.0, 9.0/280.0, 41.0/840.0, 0.0, 0.0,
+];
+
+/// The 8th order solution differs from the 7th order one by 41/840 * (k1 + k11 - k12 - k13)
+const ERR: f64 = 41.0 / 840.0;
+
+/// Runge-Kutta-Fehlberg 7th order method with embedded 8th order error estimate
+pub struct Rkf78<F> {
+	f: F,
+	h: f64,
+	x: f64,
+	y: Array1<f64>,
+	xmax: f64,
+	atol: f64,
+	rtol: f64,
+	controller: Controller,
+}
+
+impl<F> Rkf78<F> {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		atol: f64,
+		rtol: f64,
+		control: StepControl,
+	) -> Self {
+		Self {
+			f,
+			h: h.clamp(control.min_h, control.max_h),
+			x: x0,
+			y: y0.clone(),
+			xmax,
+			atol,
+			rtol,
+			controller: Controller {
+				control,
+				exponent: 1.0 / 8.0,
+				safety_factor: 0.8,
+				min_relative_dh: 0.1,
+				max_relative_dh: 4.0,
+			},
+		}
+	}
+}
+
+impl<F> Rkf78<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Take a single step of size h. Returns the new state and the normalized error estimate
+	fn step(&self, h: f64) -> Result<(Array1<f64>, f64), String> {
+		let mut k: Vec<Array1<f64>> = Vec::with_capacity(13);
+		for (c, a) in C.iter().zip(A) {
+			let mut y = self.y.clone();
+			for (a, k) in a.iter().zip(&k).filter(|(&a, _)| a != 0.0) {
+				y.scaled_add(h * a, k);
+			}
+			k.push((self.f)(self.x + c * h, &y)?);
+		}
+
+		let mut y = self.y.clone();
+		for (b, k) in B.iter().zip(&k).filter(|(&b, _)| b != 0.0) {
+			y.scaled_add(h * b, k);
+		}
+
+		let err = (&k[0] + &k[10] - &k[11] - &k[12]) * (ERR * h);
+
+		let max_y_norm = y.dot(&y).sqrt().max(self.y.dot(&self.y).sqrt());
+		let tol = self.atol + self.rtol * max_y_norm;
+		Ok((y, err.dot(&err).sqrt() / tol))
+	}
+}
+
+impl<F> Solver for Rkf78<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+
+		let (x, y, h) = self
+			.controller
+			.advance("RKF78", self.x, self.xmax, self.h, |h| self.step(h))?;
+		self.x = x;
+		self.y = y.clone();
+		self.h = h;
+		Ok(Some((x, y)))
+	}
+}
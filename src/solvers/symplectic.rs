@@ -0,0 +1,131 @@
+use super::Solver;
+use ndarray::Array1;
+
+/// Yoshida's (1990) 4th order composition weights
+const YOSHIDA4: [f64; 3] = [1.3512071919596578, -1.7024143839193155, 1.3512071919596578];
+
+/// Yoshida's (1990) 6th order composition weights (solution A)
+const YOSHIDA6: [f64; 7] = [
+	0.784513610477560,
+	0.235573213359357,
+	-1.17767998417887,
+	1.3151863206839063,
+	-1.17767998417887,
+	0.235573213359357,
+	0.784513610477560,
+];
+
+/// Composition of leapfrog steps making up a single step of a symplectic method
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Composition {
+	/// Stormer-Verlet/leapfrog (drift-kick-drift), 2nd order
+	Leapfrog,
+	/// Yoshida's composition of three leapfrog steps, 4th order
+	Yoshida4,
+	/// Yoshida's composition of seven leapfrog steps, 6th order
+	Yoshida6,
+}
+
+impl Composition {
+	/// Step size of each leapfrog step as a fraction of the full step
+	fn weights(&self) -> &'static [f64] {
+		match self {
+			Self::Leapfrog => &[1.0],
+			Self::Yoshida4 => &YOSHIDA4,
+			Self::Yoshida6 => &YOSHIDA6,
+		}
+	}
+}
+
+/// Fixed-step symplectic integrator splitting the ODE into drift (positions advanced by velocities)
+/// and kick (velocities advanced by accelerations) parts. The state is made up of blocks of
+/// block_size elements, each starting with a position and a velocity in 3 dimensions. Any further
+/// elements of a block are advanced alongside the velocities. Energy errors remain bounded over
+/// long propagations as long as accelerations depend on positions only
+pub struct Symplectic<F> {
+	f: F,
+	h: f64,
+	x: f64,
+	y: Array1<f64>,
+	xmax: f64,
+	composition: Composition,
+	/// Indices of position elements in the state vector. The velocity belonging to position i is
+	/// at i + 3
+	positions: Vec<usize>,
+}
+
+impl<F> Symplectic<F> {
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		composition: Composition,
+		block_size: usize,
+	) -> Self {
+		Self {
+			f,
+			h,
+			x: x0,
+			y: y0.clone(),
+			xmax,
+			composition,
+			positions: (0..y0.len()).filter(|i| i % block_size < 3).collect(),
+		}
+	}
+}
+
+impl<F> Symplectic<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn drift(&self, y: &mut Array1<f64>, h: f64) {
+		for &i in &self.positions {
+			y[i] += h * y[i + 3];
+		}
+	}
+
+	fn kick(&self, x: f64, y: &mut Array1<f64>, h: f64) -> Result<(), String> {
+		let mut dy = (self.f)(x, y)?;
+		for &i in &self.positions {
+			dy[i] = 0.0;
+		}
+		y.scaled_add(h, &dy);
+		Ok(())
+	}
+}
+
+impl<F> Solver for Symplectic<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+		let last_step = self.x + self.h >= self.xmax;
+		if last_step {
+			self.h = self.xmax - self.x;
+		}
+
+		let mut x = self.x;
+		let mut y = self.y.clone();
+		for w in self.composition.weights() {
+			let h = w * self.h;
+			self.drift(&mut y, 0.5 * h);
+			x += 0.5 * h;
+			self.kick(x, &mut y, h)?;
+			self.drift(&mut y, 0.5 * h);
+			x += 0.5 * h;
+		}
+
+		self.x = if last_step {
+			self.xmax
+		} else {
+			self.x + self.h
+		};
+		self.y = y.clone();
+		Ok(Some((self.x, y)))
+	}
+}
@@ -0,0 +1,246 @@
+use super::Solver;
+use ndarray::{s, Array1};
+
+/// Stumpff functions C(z) and S(z)
+fn stumpff(z: f64) -> (f64, f64) {
+	if z.abs() < 1e-3 {
+		// Series expansion, avoiding cancellation near z = 0
+		(
+			0.5 - z / 24.0 + z * z / 720.0 - z * z * z / 40320.0,
+			1.0 / 6.0 - z / 120.0 + z * z / 5040.0 - z * z * z / 362880.0,
+		)
+	} else if z > 0.0 {
+		let sz = z.sqrt();
+		((1.0 - sz.cos()) / z, (sz - sz.sin()) / (sz * z))
+	} else {
+		let sz = (-z).sqrt();
+		((sz.cosh() - 1.0) / -z, (sz.sinh() - sz) / (sz * -z))
+	}
+}
+
+/// Advance position and velocity along a Kepler orbit about a central body with gravitational
+/// parameter mu by dt, using universal variables
+fn kepler_drift(
+	mu: f64,
+	r0: &Array1<f64>,
+	v0: &Array1<f64>,
+	dt: f64,
+) -> Result<(Array1<f64>, Array1<f64>), String> {
+	let r0_norm = r0.dot(r0).sqrt();
+	let sqrt_mu = mu.sqrt();
+	let sigma0 = r0.dot(v0) / sqrt_mu;
+	// Reciprocal of the semi-major axis
+	let alpha = 2.0 / r0_norm - v0.dot(v0) / mu;
+
+	// Solve the universal Kepler equation for chi with Newton's method
+	let mut chi = sqrt_mu * dt / r0_norm;
+	let mut converged = false;
+	for _ in 0..50 {
+		let z = alpha * chi * chi;
+		let (c, s) = stumpff(z);
+		let r = sigma0 * chi * (1.0 - z * s) + (1.0 - alpha * r0_norm) * chi * chi * c + r0_norm;
+		let t =
+			sigma0 * chi * chi * c + (1.0 - alpha * r0_norm) * chi * chi * chi * s + r0_norm * chi;
+		let delta = (t - sqrt_mu * dt) / r;
+		chi -= delta;
+		if delta.abs() <= 1e-15 * chi.abs().max(1e-300) {
+			converged = true;
+			break;
+		}
+	}
+	if !converged {
+		return Err(format!(
+			"Wisdom-Holman: Kepler drift over {dt} s did not converge"
+		));
+	}
+
+	let z = alpha * chi * chi;
+	let (c, s) = stumpff(z);
+	let f = 1.0 - chi * chi / r0_norm * c;
+	let g = dt - chi * chi * chi / sqrt_mu * s;
+	let r = f * r0 + g * v0;
+	let r_norm = r.dot(&r).sqrt();
+	let f_dot = sqrt_mu / (r_norm * r0_norm) * (z * s - 1.0) * chi;
+	let g_dot = 1.0 - chi * chi / r_norm * c;
+	let v = f_dot * r0 + g_dot * v0;
+	Ok((r, v))
+}
+
+/// Wisdom-Holman symplectic map in democratic heliocentric coordinates (Duncan, Levison & Lee
+/// 1998). The motion of each body about the central body, the first one in the state vector, is
+/// solved analytically, so steps can be a sizeable fraction of the shortest orbital period.
+/// Interactions between the other bodies and any perturbations are applied as kicks.
+///
+/// The state is made up of blocks of block_size elements, each starting with a position and a
+/// velocity in 3 dimensions. Any further elements of a block are advanced alongside the velocities
+pub struct WisdomHolman<F> {
+	f: F,
+	h: f64,
+	x: f64,
+	xmax: f64,
+	/// Gravitational parameters of all bodies (0 for massless ones)
+	mus: Vec<f64>,
+	block_size: usize,
+	/// Heliocentric positions and barycentric velocities of all but the central body, whose
+	/// block holds the position and velocity of the barycenter instead
+	dh: Array1<f64>,
+}
+
+impl<F> WisdomHolman<F> {
+	pub fn new(
+		f: F,
+		h: f64,
+		x0: f64,
+		y0: &Array1<f64>,
+		xmax: f64,
+		mus: &[f64],
+		block_size: usize,
+	) -> Self {
+		let mut solver = Self {
+			f,
+			h,
+			x: x0,
+			xmax,
+			mus: mus.to_vec(),
+			block_size,
+			dh: Array1::zeros(0),
+		};
+		solver.dh = solver.to_democratic_heliocentric(y0);
+		solver
+	}
+
+	fn position(&self, y: &Array1<f64>, i: usize) -> Array1<f64> {
+		let k = i * self.block_size;
+		y.slice(s![k..k + 3]).to_owned()
+	}
+
+	fn velocity(&self, y: &Array1<f64>, i: usize) -> Array1<f64> {
+		let k = i * self.block_size;
+		y.slice(s![k + 3..k + 6]).to_owned()
+	}
+
+	fn to_democratic_heliocentric(&self, y: &Array1<f64>) -> Array1<f64> {
+		let total_mu = self.mus.iter().sum::<f64>();
+		let mut barycenter = Array1::zeros(6);
+		for (i, mu) in self.mus.iter().enumerate() {
+			let k = i * self.block_size;
+			barycenter.scaled_add(mu / total_mu, &y.slice(s![k..k + 6]));
+		}
+
+		let mut dh = y.clone();
+		let (x0, v_cm) = (self.position(y, 0), barycenter.slice(s![3..]).to_owned());
+		dh.slice_mut(s![..6]).assign(&barycenter);
+		for i in 1..self.mus.len() {
+			let k = i * self.block_size;
+			dh.slice_mut(s![k..k + 3])
+				.assign(&(self.position(y, i) - &x0));
+			dh.slice_mut(s![k + 3..k + 6])
+				.assign(&(self.velocity(y, i) - &v_cm));
+		}
+		dh
+	}
+
+	fn to_inertial(&self, dh: &Array1<f64>) -> Array1<f64> {
+		let total_mu = self.mus.iter().sum::<f64>();
+		let (mut x0, mut v0) = (self.position(dh, 0), self.velocity(dh, 0));
+		let v_cm = v0.clone();
+		for (i, mu) in self.mus.iter().enumerate().skip(1) {
+			x0.scaled_add(-mu / total_mu, &self.position(dh, i));
+			v0.scaled_add(-mu / self.mus[0], &self.velocity(dh, i));
+		}
+
+		let mut y = dh.clone();
+		y.slice_mut(s![..3]).assign(&x0);
+		y.slice_mut(s![3..6]).assign(&v0);
+		for i in 1..self.mus.len() {
+			let k = i * self.block_size;
+			y.slice_mut(s![k..k + 3])
+				.assign(&(self.position(dh, i) + &x0));
+			y.slice_mut(s![k + 3..k + 6])
+				.assign(&(self.velocity(dh, i) + &v_cm));
+		}
+		y
+	}
+
+	/// Shift heliocentric positions by the momentum of the central body
+	fn jump(&mut self, h: f64) {
+		let mut shift = Array1::zeros(3);
+		for (i, mu) in self.mus.iter().enumerate().skip(1) {
+			shift.scaled_add(h * mu / self.mus[0], &self.velocity(&self.dh, i));
+		}
+		for i in 1..self.mus.len() {
+			let k = i * self.block_size;
+			let mut position = self.dh.slice_mut(s![k..k + 3]);
+			position += &shift;
+		}
+	}
+
+	/// Advance each body along its Kepler orbit about the central body, and the barycenter
+	/// along a straight line
+	fn kepler(&mut self, h: f64) -> Result<(), String> {
+		let v_cm = self.velocity(&self.dh, 0);
+		self.dh.slice_mut(s![..3]).scaled_add(h, &v_cm);
+		for i in 1..self.mus.len() {
+			let k = i * self.block_size;
+			let (r, v) = kepler_drift(
+				self.mus[0],
+				&self.position(&self.dh, i),
+				&self.velocity(&self.dh, i),
+				h,
+			)?;
+			self.dh.slice_mut(s![k..k + 3]).assign(&r);
+			self.dh.slice_mut(s![k + 3..k + 6]).assign(&v);
+		}
+		Ok(())
+	}
+}
+
+impl<F> WisdomHolman<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	/// Apply all accelerations except the central body's pull on the others, which the Kepler
+	/// drift accounts for
+	fn kick(&mut self, x: f64, h: f64) -> Result<(), String> {
+		let mut dy = (self.f)(x, &self.to_inertial(&self.dh))?;
+		for i in 0..self.mus.len() {
+			let k = i * self.block_size;
+			dy.slice_mut(s![k..k + 3]).fill(0.0);
+			if i > 0 {
+				let r = self.position(&self.dh, i);
+				let kepler = -self.mus[0] / r.dot(&r).sqrt().powi(3) * r;
+				let mut a = dy.slice_mut(s![k + 3..k + 6]);
+				a -= &kepler;
+			}
+		}
+		// The barycenter moves uniformly
+		dy.slice_mut(s![3..6]).fill(0.0);
+		self.dh.scaled_add(h, &dy);
+		Ok(())
+	}
+}
+
+impl<F> Solver for WisdomHolman<F>
+where
+	F: Fn(f64, &Array1<f64>) -> Result<Array1<f64>, String>,
+{
+	fn next_state(&mut self) -> Result<Option<(f64, Array1<f64>)>, String> {
+		if self.x >= self.xmax {
+			return Ok(None);
+		}
+		let last_step = self.x + self.h >= self.xmax;
+		if last_step {
+			self.h = self.xmax - self.x;
+		}
+
+		let h = self.h;
+		self.kick(self.x, 0.5 * h)?;
+		self.jump(0.5 * h);
+		self.kepler(h)?;
+		self.jump(0.5 * h);
+		self.kick(self.x + h, 0.5 * h)?;
+
+		self.x = if last_step { self.xmax } else { self.x + h };
+		Ok(Some((self.x, self.to_inertial(&self.dh))))
+	}
+}
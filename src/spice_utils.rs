@@ -69,6 +69,133 @@ pub fn mu(body: i32) -> Result<f64, String> {
 	Ok(value * 1e9)
 }
 
+/// Retrieve values of a body-specific kernel pool variable (e. g. "RADII" or "J2")
+pub fn body_constants(body: i32, item: &str, maxn: usize) -> Result<Vec<f64>, String> {
+	set_error_handling("return", "short", "NULL");
+
+	let mut dim = 0;
+	let mut values = vec![0f64; maxn];
+
+	unsafe {
+		spice::c::bodvrd_c(
+			spice::cstr!(body.to_string()),
+			spice::cstr!(item),
+			maxn as i32,
+			&mut dim,
+			values.as_mut_ptr(),
+		);
+	};
+
+	get_spice_result_and_reset()
+		.map_err(|msg| format!("Could not retrieve {item} for body {body}: {msg}"))?;
+
+	values.truncate(dim as usize);
+	Ok(values)
+}
+
+/// Whether a body-specific kernel pool variable is defined
+pub fn has_body_constant(body: i32, item: &str) -> bool {
+	unsafe { spice::c::bodfnd_c(body, spice::cstr!(item)) != 0 }
+}
+
+/// Retrieve zonal harmonic coefficients J2..Jn and their reference radius (equatorial radius) in m
+pub fn zonal_harmonics(body: i32, max_degree: usize) -> Result<(f64, Vec<f64>), String> {
+	let radius = body_constants(body, "RADII", 3)?[0] * 1000f64;
+
+	let mut js = Vec::with_capacity(max_degree - 1);
+	for n in 2..=max_degree {
+		js.push(body_constants(body, &format!("J{n}"), 1)?[0]);
+	}
+
+	Ok((radius, js))
+}
+
+/// Format et as an ISO calendar UTC timestamp with millisecond precision
+pub fn et2utc(et: f64) -> String {
+	let mut utc = [0 as c_char; 32];
+
+	unsafe {
+		spice::c::et2utc_c(et, spice::cstr!("ISOC"), 3, 32, utc.as_mut_ptr());
+	}
+
+	unsafe { CStr::from_ptr(utc.as_ptr()) }
+		.to_str()
+		.unwrap()
+		.to_string()
+}
+
+/// Name of a body (e. g. "EARTH"), or its NAIF-ID if it has none
+pub fn body_name(body: i32) -> String {
+	let mut name = [0 as c_char; 37];
+	let mut found = 0;
+
+	unsafe {
+		spice::c::bodc2n_c(body, 37, name.as_mut_ptr(), &mut found);
+	}
+
+	if found == 0 {
+		return body.to_string();
+	}
+
+	unsafe { CStr::from_ptr(name.as_ptr()) }
+		.to_str()
+		.unwrap()
+		.to_string()
+}
+
+/// Retrieve name of the body-fixed reference frame associated with body (e. g. "IAU_EARTH")
+pub fn body_fixed_frame(body: i32) -> Result<String, String> {
+	let mut frcode = 0;
+	let mut frname = [0 as c_char; 33];
+	let mut found = 0;
+
+	unsafe {
+		spice::c::cidfrm_c(body, 33, &mut frcode, frname.as_mut_ptr(), &mut found);
+	}
+
+	if found == 0 {
+		return Err(format!("No body-fixed frame associated with body {body}"));
+	}
+
+	Ok(unsafe { CStr::from_ptr(frname.as_ptr()) }
+		.to_str()
+		.unwrap()
+		.to_string())
+}
+
+/// Retrieve matrix rotating position vectors from frame 'from' to frame 'to' at et
+pub fn rotation(from: &str, to: &str, et: f64) -> Result<[[f64; 3]; 3], String> {
+	set_error_handling("return", "short", "NULL");
+
+	let mut rot = [[0f64; 3]; 3];
+
+	unsafe {
+		spice::c::pxform_c(spice::cstr!(from), spice::cstr!(to), et, rot.as_mut_ptr());
+	}
+
+	get_spice_result_and_reset()
+		.map_err(|msg| format!("Could not retrieve rotation from {from} to {to} at {et}: {msg}"))?;
+
+	Ok(rot)
+}
+
+/// Retrieve matrix transforming state vectors from frame 'from' to frame 'to' at et
+pub fn state_transformation(from: &str, to: &str, et: f64) -> Result<[[f64; 6]; 6], String> {
+	set_error_handling("return", "short", "NULL");
+
+	let mut xform = [[0f64; 6]; 6];
+
+	unsafe {
+		spice::c::sxform_c(spice::cstr!(from), spice::cstr!(to), et, xform.as_mut_ptr());
+	}
+
+	get_spice_result_and_reset().map_err(|msg| {
+		format!("Could not retrieve state transformation from {from} to {to} at {et}: {msg}")
+	})?;
+
+	Ok(xform)
+}
+
 /// Retrieve state vector for body relative to central body at t
 pub fn state_at_instant(body: i32, cb_id: i32, et: f64) -> Result<Array1<f64>, String> {
 	set_error_handling("return", "short", "NULL");
@@ -79,22 +206,31 @@ pub fn state_at_instant(body: i32, cb_id: i32, et: f64) -> Result<Array1<f64>, S
 	get_spice_result_and_reset().map_err(|msg| {
 		format!("Could not retrieve state of {body} relative to {cb_id} at {et}: {msg}")
 	})?;
-	Ok(arr1(&pos))
+
+	// Unit conversion: km and km/s to m and m/s
+	Ok(arr1(&pos) * 1000f64)
 }
 
-/// Retrieve state vectors of specified bodies at et
-pub fn states_at_instant(bodies: &[i32], cb_id: i32, et: f64) -> Result<Array1<f64>, String> {
-	let mut state = ndarray::Array1::zeros(bodies.len() * 6);
+/// Retrieve state vectors of specified bodies at et. Each body occupies block_size elements of the
+/// returned vector, starting with its position and velocity; any others are zero
+pub fn states_at_instant(
+	bodies: &[i32],
+	cb_id: i32,
+	et: f64,
+	block_size: usize,
+) -> Result<Array1<f64>, String> {
+	let mut state = ndarray::Array1::zeros(bodies.len() * block_size);
 
 	for (idx, &b) in bodies.iter().enumerate() {
-		let mut s = state.slice_mut(s![(idx * 6)..(idx * 6 + 6)]);
+		let mut s = state.slice_mut(s![(idx * block_size)..(idx * block_size + 6)]);
 		s += &state_at_instant(b, cb_id, et)?;
 	}
 
 	Ok(state)
 }
 
-/// Write data to SPK file
+/// Write data to SPK file. Each body occupies block_size elements of the state vectors, starting
+/// with its position and velocity
 pub fn write_to_spk(
 	fname: &str,
 	bodies: &[i32],
@@ -102,6 +238,7 @@ pub fn write_to_spk(
 	ets: &[f64],
 	cb_id: i32,
 	fraction_to_save: f32,
+	block_size: usize,
 ) -> Result<(), String> {
 	set_error_handling("return", "short", "NULL");
 
@@ -129,19 +266,36 @@ pub fn write_to_spk(
 
 	// Extract states to actually write to the file
 	let steps_to_skip = (1f32 / fraction_to_save) as usize;
+	// Always keep the last state so the segment covers the whole propagated interval
+	let last = ets.len() - 1;
+	// usize::is_multiple_of is too recent for older toolchains
+	#[allow(clippy::manual_is_multiple_of)]
+	let keep = |i: usize| i % steps_to_skip == 0 || i == last;
 	let mut ets = ets
 		.iter()
-		.step_by(steps_to_skip)
-		.cloned()
+		.enumerate()
+		.filter(|&(i, _)| keep(i))
+		.map(|(_, &et)| et)
 		.collect::<Vec<_>>();
-	let states = states.iter().step_by(steps_to_skip).collect::<Vec<_>>();
+	let mut states = states
+		.iter()
+		.enumerate()
+		.filter(|&(i, _)| keep(i))
+		.map(|(_, s)| s)
+		.collect::<Vec<_>>();
+
+	// Backward propagations yield descending epochs, but SPK segments need them ascending
+	if ets[0] > ets[ets.len() - 1] {
+		ets.reverse();
+		states.reverse();
+	}
 
 	// If the observing bodies trajectory was also propagated, assemble a state matrix for that body
 	// that can be substracted from other bodies state matrices to yield state relative to observing body
 	let cb_states_matrix_km = bodies.iter().position(|&id| id == cb_id).map(|idx| {
 		let cb_states = states
 			.iter()
-			.map(|&s| s.slice(s![(idx * 6)..(idx * 6 + 6)]))
+			.map(|&s| s.slice(s![(idx * block_size)..(idx * block_size + 6)]))
 			.collect::<Vec<_>>();
 
 		concatenate(Axis(0), &cb_states).unwrap() / 1000f64
@@ -156,7 +310,7 @@ pub fn write_to_spk(
 		// Create state matrix for current target body with states in km and km/s
 		let body_states = states
 			.iter()
-			.map(|&s| s.slice(s![(idx * 6)..(idx * 6 + 6)]))
+			.map(|&s| s.slice(s![(idx * block_size)..(idx * block_size + 6)]))
 			.collect::<Vec<_>>();
 
 		let mut states_matrix_km = (concatenate(Axis(0), &body_states[..]).unwrap()) / 1000f64;
@@ -181,7 +335,8 @@ pub fn write_to_spk(
 			// Segment identifier
 			&format!("Position of {} relative to {}", id, cb_id),
 			// Degree of polynomial to be used for lagrange interpolation. Currently somewhat arbitrary.
-			7,
+			// Short segments between maneuvers may not have enough states for degree 7
+			7.min(body_states.len() as i32 - 1),
 			// Number of states/epochs
 			body_states.len() as i32,
 			// Pointer to beginning of state matrix
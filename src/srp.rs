@@ -0,0 +1,124 @@
+use crate::constants::AU;
+use crate::spice_utils;
+use ndarray::Array1;
+use std::f64::consts::PI;
+
+/// Solar radiation pressure at 1 AU in N/m^2
+const P_SUN_1AU: f64 = 4.56e-6;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShadowModel {
+	Cylindrical,
+	Conical,
+}
+
+/// Area (m^2), mass (kg) and reflectivity coefficient
+pub type Parameters = [f64; 3];
+
+/// Cannonball model parameters of a single small body
+pub struct Spacecraft {
+	/// Index of the body in the state vector
+	pub idx: usize,
+	/// Cross-sectional area in m^2
+	pub area: f64,
+	/// Mass in kg, unless tracked in the state vector
+	pub mass: f64,
+	/// Reflectivity coefficient
+	pub cr: f64,
+}
+
+impl Spacecraft {
+	/// Use the given parameters (area, mass, Cr) or retrieve them from the kernel pool
+	/// (BODYnnn_SRP_AREA, BODYnnn_MASS, BODYnnn_SRP_CR)
+	pub fn new(body: i32, idx: usize, params: Option<Parameters>) -> Result<Self, String> {
+		let [area, mass, cr] = match params {
+			Some(params) => params,
+			None => [
+				spice_utils::body_constants(body, "SRP_AREA", 1)?[0],
+				spice_utils::body_constants(body, "MASS", 1)?[0],
+				spice_utils::body_constants(body, "SRP_CR", 1)?[0],
+			],
+		};
+		Ok(Self {
+			idx,
+			area,
+			mass,
+			cr,
+		})
+	}
+}
+
+/// Solar radiation pressure acting on small bodies, with optional shadowing by occulting bodies
+pub struct SrpModel {
+	pub spacecraft: Vec<Spacecraft>,
+	pub shadow: Option<ShadowModel>,
+	/// Radius of the Sun in m
+	pub sun_radius: f64,
+	/// NAIF-IDs and radii in m of bodies that may cast shadows
+	pub occulters: Vec<(i32, f64)>,
+}
+
+/// Acceleration due to solar radiation pressure on a body of given mass at position r (relative to
+/// the Sun), scaled by the fraction of the solar disk visible from the body
+pub fn acceleration(sc: &Spacecraft, mass: f64, r: &Array1<f64>, illumination: f64) -> Array1<f64> {
+	let r_norm = r.dot(r).sqrt();
+	let p = P_SUN_1AU * (AU / r_norm).powi(2);
+	(illumination * p * sc.cr * sc.area / mass / r_norm) * r
+}
+
+/// Whether an occulter may hide part of the solar disk from a body, given vectors from it to the
+/// Sun and occulter - only if the occulter is nearer than the Sun and lies on the Sun's side
+pub fn may_occult(to_sun: &Array1<f64>, to_occulter: &Array1<f64>) -> bool {
+	to_occulter.dot(to_occulter) < to_sun.dot(to_sun) && to_sun.dot(to_occulter) > 0f64
+}
+
+/// Fraction of the solar disk visible from a body, given vectors from it to the Sun and occulter
+pub fn illumination(
+	model: ShadowModel,
+	to_sun: &Array1<f64>,
+	to_occulter: &Array1<f64>,
+	sun_radius: f64,
+	occulter_radius: f64,
+) -> f64 {
+	if !may_occult(to_sun, to_occulter) {
+		return 1f64;
+	}
+
+	let d_sun = to_sun.dot(to_sun).sqrt();
+	let d_occ = to_occulter.dot(to_occulter).sqrt();
+	let cos_c = to_sun.dot(to_occulter) / (d_sun * d_occ);
+
+	match model {
+		ShadowModel::Cylindrical => {
+			// In shadow if behind the occulter within a cylinder of its radius along the Sun direction
+			let along = to_occulter.dot(to_sun) / d_sun;
+			if d_occ * d_occ - along * along < occulter_radius * occulter_radius {
+				0f64
+			} else {
+				1f64
+			}
+		}
+		ShadowModel::Conical => {
+			// Apparent radii of Sun and occulter and their apparent separation
+			let a = (sun_radius / d_sun).asin();
+			let b = (occulter_radius / d_occ).min(1f64).asin();
+			let c = cos_c.clamp(-1f64, 1f64).acos();
+
+			if c >= a + b {
+				1f64
+			} else if c <= b - a {
+				// Umbra
+				0f64
+			} else if c <= a - b {
+				// Annular eclipse
+				1f64 - (b * b) / (a * a)
+			} else {
+				// Penumbra - area of overlap of two discs
+				let x = (c * c + a * a - b * b) / (2f64 * c);
+				let y = (a * a - x * x).max(0f64).sqrt();
+				let overlap = a * a * (x / a).acos() + b * b * ((c - x) / b).acos() - c * y;
+				1f64 - overlap / (PI * a * a)
+			}
+		}
+	}
+}
@@ -0,0 +1,194 @@
+use crate::spice_utils;
+use ndarray::{arr1, s, Array1};
+
+/// Standard gravity in m/s^2, relating specific impulse to exhaust velocity
+const G0: f64 = 9.80665;
+
+/// Direction law of a thrust arc
+pub enum Direction {
+	/// Fixed direction in J2000
+	Inertial([f64; 3]),
+	/// Along the velocity relative to the observing body
+	Velocity,
+	/// Against the velocity relative to the observing body
+	AntiVelocity,
+	/// Directions in J2000 at given epochs, interpolated linearly
+	Tabulated {
+		ets: Vec<f64>,
+		directions: Vec<[f64; 3]>,
+	},
+}
+
+impl Direction {
+	/// Parse 'velocity', 'anti-velocity', 'inertial:X:Y:Z' or 'table:FILE'
+	pub fn from_spec(spec: &str) -> Result<Self, String> {
+		let invalid = || {
+			format!("Invalid thrust direction '{spec}' - Expected velocity, anti-velocity, inertial:X:Y:Z or table:FILE")
+		};
+
+		match spec.split_once(':') {
+			None if spec == "velocity" => Ok(Self::Velocity),
+			None if spec == "anti-velocity" => Ok(Self::AntiVelocity),
+			Some(("inertial", params)) => {
+				let params = params
+					.split(':')
+					.map(str::parse::<f64>)
+					.collect::<Result<Vec<_>, _>>()
+					.map_err(|_| invalid())?;
+				match params[..] {
+					[x, y, z] if x != 0f64 || y != 0f64 || z != 0f64 => {
+						Ok(Self::Inertial([x, y, z]))
+					}
+					_ => Err(invalid()),
+				}
+			}
+			Some(("table", path)) => Self::from_file(path),
+			_ => Err(invalid()),
+		}
+	}
+
+	/// Load tabulated directions from a text file with one 'UTC_TIMESTAMP X Y Z' line per epoch.
+	/// Empty lines and lines starting with '#' are ignored
+	pub fn from_file(path: &str) -> Result<Self, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("Could not read thrust direction table '{path}': {e}"))?;
+
+		let (mut ets, mut directions) = (Vec::new(), Vec::new());
+		for line in contents.lines().map(str::trim) {
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let invalid = || format!("Invalid line in thrust direction table '{path}': '{line}'");
+
+			let (epoch, values) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+			let values = values
+				.split_whitespace()
+				.map(str::parse::<f64>)
+				.collect::<Result<Vec<_>, _>>()
+				.map_err(|_| invalid())?;
+			match values[..] {
+				[x, y, z] => {
+					ets.push(spice::str2et(epoch));
+					directions.push([x, y, z]);
+				}
+				_ => return Err(invalid()),
+			}
+		}
+
+		if ets.is_empty() {
+			return Err(format!("Thrust direction table '{path}' is empty"));
+		} else if ets.windows(2).any(|w| w[0] >= w[1]) {
+			return Err(format!(
+				"Epochs in thrust direction table '{path}' must be ascending"
+			));
+		}
+
+		Ok(Self::Tabulated { ets, directions })
+	}
+
+	/// Unit thrust direction in J2000, given the body's state relative to the observing body
+	pub fn unit_vector(&self, et: f64, rel_state: &Array1<f64>) -> Array1<f64> {
+		let dir = match self {
+			Self::Inertial(dir) => arr1(dir),
+			Self::Velocity => rel_state.slice(s![3..6]).to_owned(),
+			Self::AntiVelocity => -rel_state.slice(s![3..6]).to_owned(),
+			Self::Tabulated { ets, directions } => {
+				let n = ets.len();
+				if et <= ets[0] {
+					arr1(&directions[0])
+				} else if et >= ets[n - 1] {
+					arr1(&directions[n - 1])
+				} else {
+					let i = ets.partition_point(|&t| t < et) - 1;
+					let frac = (et - ets[i]) / (ets[i + 1] - ets[i]);
+					arr1(&directions[i]) * (1f64 - frac) + arr1(&directions[i + 1]) * frac
+				}
+			}
+		};
+		&dir / dir.dot(&dir).sqrt()
+	}
+}
+
+/// Continuous thrust of a small body between two epochs
+pub struct ThrustArc {
+	pub body: i32,
+	pub start: f64,
+	pub stop: f64,
+	/// Thrust in N
+	pub thrust: f64,
+	/// Specific impulse in s
+	pub isp: f64,
+	pub direction: Direction,
+}
+
+impl ThrustArc {
+	/// Parse a line of the form 'START_UTC STOP_UTC BODY THRUST ISP DIRECTION', thrust in N and
+	/// specific impulse in s
+	pub fn parse(line: &str) -> Result<Self, String> {
+		let invalid = || {
+			format!("Invalid thrust arc '{line}' - Expected START_UTC STOP_UTC BODY THRUST ISP DIRECTION")
+		};
+
+		let fields = line.split_whitespace().collect::<Vec<_>>();
+		let (start, stop, body, thrust, isp, direction) = match fields[..] {
+			[start, stop, body, thrust, isp, direction] => {
+				(start, stop, body, thrust, isp, direction)
+			}
+			_ => return Err(invalid()),
+		};
+
+		let thrust = thrust.parse::<f64>().map_err(|_| invalid())?;
+		let isp = isp.parse::<f64>().map_err(|_| invalid())?;
+		if thrust < 0f64 || isp <= 0f64 {
+			return Err(invalid());
+		}
+
+		let (start, stop) = (spice::str2et(start), spice::str2et(stop));
+		if start >= stop {
+			return Err(format!("Thrust arc '{line}' must start before it stops"));
+		}
+
+		Ok(Self {
+			body: spice_utils::naif_ids(&[body])?[0],
+			start,
+			stop,
+			thrust,
+			isp,
+			direction: Direction::from_spec(direction)?,
+		})
+	}
+
+	/// Read thrust arcs from a file with one arc per line, sorted by start epoch.
+	/// Empty lines and lines starting with '#' are ignored
+	pub fn from_file(path: &str) -> Result<Vec<Self>, String> {
+		let contents = std::fs::read_to_string(path)
+			.map_err(|e| format!("Could not read thrust arc file '{path}': {e}"))?;
+
+		let mut arcs = contents
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(Self::parse)
+			.collect::<Result<Vec<_>, _>>()?;
+
+		arcs.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+		Ok(arcs)
+	}
+
+	/// Whether the engine is firing at et
+	pub fn is_active(&self, et: f64) -> bool {
+		(self.start..self.stop).contains(&et)
+	}
+
+	/// Rate of change of the body's mass in kg/s while the engine is firing
+	pub fn mass_flow(&self) -> f64 {
+		-self.thrust / (self.isp * G0)
+	}
+
+	/// Acceleration of a body with given mass and state relative to the observing body, and its
+	/// rate of change of mass
+	pub fn acceleration(&self, et: f64, rel_state: &Array1<f64>, mass: f64) -> (Array1<f64>, f64) {
+		let a = self.thrust / mass * self.direction.unit_vector(et, rel_state);
+		(a, self.mass_flow())
+	}
+}
@@ -0,0 +1,58 @@
+use crate::ode::STATE_SIZE;
+use ndarray::{s, Array1, Array2, ArrayView1, Axis};
+
+/// Number of state vector blocks holding the state transition matrix of a small body, one per
+/// column. Each block holds the variations of position and velocity in place of a body's position
+/// and velocity, so that solvers integrate them like any other body
+pub const STM_BLOCKS: usize = 6;
+
+/// Variation of position in m used for central differences of accelerations
+pub const POSITION_STEP: f64 = 1f64;
+/// Variation of velocity in m/s used for central differences of accelerations
+pub const VELOCITY_STEP: f64 = 1e-3;
+
+/// Partial derivatives of the acceleration due to a point mass with respect to the position of
+/// the accelerated body, given the vector r from the body to the point mass
+pub fn gravity_gradient(mu: f64, r: &Array1<f64>) -> Array2<f64> {
+	let r_norm = r.dot(r).sqrt();
+	let r_col = r.view().insert_axis(Axis(1));
+	let outer = r_col.dot(&r_col.t());
+	mu / r_norm.powi(5) * (3f64 * outer - r_norm.powi(2) * Array2::<f64>::eye(3))
+}
+
+/// Derivative of the variational blocks of a small body, given the partial derivatives of its
+/// acceleration with respect to its position (a) and velocity (b)
+pub fn derivative(blocks: &ArrayView1<f64>, a: &Array2<f64>, b: &Array2<f64>) -> Array1<f64> {
+	let mut derivative = Array1::zeros(blocks.len());
+	for column in 0..STM_BLOCKS {
+		let k = column * STATE_SIZE;
+		let dr = blocks.slice(s![k..(k + 3)]);
+		let dv = blocks.slice(s![(k + 3)..(k + 6)]);
+		derivative.slice_mut(s![k..(k + 3)]).assign(&dv);
+		derivative
+			.slice_mut(s![(k + 3)..(k + 6)])
+			.assign(&(a.dot(&dr) + b.dot(&dv)));
+	}
+	derivative
+}
+
+/// Initial variational blocks of a small body - the identity
+pub fn identity() -> Array1<f64> {
+	let mut blocks = Array1::zeros(STM_BLOCKS * STATE_SIZE);
+	for column in 0..STM_BLOCKS {
+		blocks[column * STATE_SIZE + column] = 1f64;
+	}
+	blocks
+}
+
+/// State transition matrix of the k-th small body whose variational equations are propagated,
+/// read from a state vector of the given number of bodies
+pub fn stm(state: &Array1<f64>, bodies: usize, k: usize) -> Array2<f64> {
+	let start = (bodies + k * STM_BLOCKS) * STATE_SIZE;
+	let mut stm = Array2::zeros((6, 6));
+	for column in 0..STM_BLOCKS {
+		let k = start + column * STATE_SIZE;
+		stm.column_mut(column).assign(&state.slice(s![k..(k + 6)]));
+	}
+	stm
+}